@@ -0,0 +1,71 @@
+//! Diffs large flat SVGs (many children under one group, like Graphviz output)
+//! and prints how long it takes for growing sizes.
+//!
+//! Run with `cargo bench --bench large_flat`.
+//! With the hash-indexed matching the time grows about linearly with the size.
+//! The diff is timed with the default rules and with only the `all` rule,
+//! which matches the children by one hash.
+
+use std::time::Instant;
+use svg_diff::config::Config;
+use svg_diff::diff_from_strings;
+
+/// The nodes of a flat SVG, each a group with a shape and a label.
+/// Every `change_every`th node is shifted, so it only matches by the weaker rules.
+fn nodes(count: usize, shift: usize, change_every: Option<usize>) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let n = (i + shift) % count;
+            let x = match change_every {
+                Some(every) if n % every == 0 => n + 1,
+                _ => n,
+            };
+            format!(
+                "<g class=\"node\"><rect x=\"{}\" y=\"{}\" width=\"10\" height=\"10\"/><text x=\"{}\">node{}</text></g>",
+                x,
+                n % 100,
+                x,
+                n
+            )
+        })
+        .collect()
+}
+
+/// A flat SVG with the nodes under one group.
+fn flat_svg(nodes: &[String]) -> String {
+    format!(
+        "<svg viewBox=\"0 0 1000 1000\"><g>{}</g></svg>",
+        nodes.concat()
+    )
+}
+
+fn millis(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Prints how long the diffs of growing flat SVGs take with the `config`.
+fn time_diffs(config: &Config) {
+    for count in [250, 500, 1000, 2000, 3000] {
+        let origin = flat_svg(&nodes(count, 0, None));
+        let target = flat_svg(&nodes(count, count / 3, Some(7)));
+        let start = Instant::now();
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], config).unwrap();
+        println!(
+            "{:>5} children: {:>8.1} ms ({} steps)",
+            count,
+            millis(start),
+            diffs[0].len()
+        );
+    }
+}
+
+fn main() {
+    println!("diff with the default rules:");
+    time_diffs(&Config::default());
+
+    println!("diff with only the all rule:");
+    let mut config = Config::default();
+    config.matching.priorities = vec!["all".to_string()];
+    config.matching.cross_parent_priorities = Vec::new();
+    time_diffs(&config);
+}
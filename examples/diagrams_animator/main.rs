@@ -18,8 +18,11 @@ use git_graph_to_svg::options::layout::LayoutOptions;
 use log::info;
 use pikchr::PikchrFlags;
 use serde::{Deserialize, Serialize};
-use svg_diff::config::Config;
-use svg_diff::{config::MatchingRule, config::MatchingRules, diff_from_strings, DiffStep};
+use svg_diff::config::{presets, Config};
+use svg_diff::{
+    config::MatchingRule, config::MatchingRules, diff_from_strings_with_reports, DiffStep,
+    MatchingReport,
+};
 
 struct AppState {
     last_svg: Mutex<String>,
@@ -41,18 +44,15 @@ async fn root() -> Result<NamedFile> {
 }
 
 #[get("/default_rules")]
-async fn default_rules() -> Result<NamedFile> {
-    // Serve one of the possible pathes ...
-    for possible_path in &[
-        "./default_matching_rules.yml",
-        "./diagrams_animator/default_matching_rules.yml",
-        "./examples/diagrams_animator/default_matching_rules.yml",
-    ] {
-        if Path::new(possible_path).exists() {
-            return Ok(NamedFile::open(possible_path)?);
-        }
-    }
-    Ok(NamedFile::open("./default_matching_rules.yml")?)
+async fn default_rules() -> HttpResponse {
+    let preset = presets::preset("pikchr").unwrap();
+    HttpResponse::Ok().body(serde_yaml::to_string(&preset.rules).unwrap())
+}
+
+#[get("/default_priorities")]
+async fn default_priorities() -> HttpResponse {
+    let preset = presets::preset("pikchr").unwrap();
+    HttpResponse::Ok().body(preset.priorities.join("\n"))
 }
 
 #[get("/js_assets/animator.js")]
@@ -74,6 +74,7 @@ async fn animator_js() -> Result<NamedFile> {
 struct ResultObject {
     svg: String,
     diffs: Vec<DiffStep>,
+    report: MatchingReport,
 }
 
 #[get("/svg")]
@@ -101,6 +102,17 @@ async fn new_pikchr_diagram(
         Ok(r) => r,
         Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
     };
+    let config = Config {
+        matching: MatchingRules {
+            rules,
+            priorities: payload.priorities.clone(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    if let Err(e) = config.validate() {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
 
     // Convert the payload to svg
     let res = pikchr::Pikchr::render(&payload.diagram, None, PikchrFlags::default());
@@ -123,16 +135,8 @@ async fn new_pikchr_diagram(
     *old_svg = svg.clone();
 
     // Retrieve the old svg
-    let (new_svgs, diffs) = {
-        match diff_from_strings(
-            &[start_svg, svg],
-            &Config {
-                matching: MatchingRules {
-                    rules,
-                    priorities: payload.priorities.clone(),
-                },
-            },
-        ) {
+    let (new_svgs, diffs, reports) = {
+        match diff_from_strings_with_reports(&[start_svg, svg], &config) {
             Ok(r) => r,
             Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
         }
@@ -141,6 +145,7 @@ async fn new_pikchr_diagram(
     HttpResponse::Ok().json(ResultObject {
         svg: new_svgs[0].clone(),
         diffs: diffs[0].clone(),
+        report: reports[0].clone(),
     })
 }
 
@@ -154,6 +159,17 @@ async fn new_git_diagram(
         Ok(r) => r,
         Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
     };
+    let config = Config {
+        matching: MatchingRules {
+            rules,
+            priorities: payload.priorities.clone(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    if let Err(e) = config.validate() {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
 
     // Convert the payload to svg
     let res = git_graph_to_svg::parse_git_instructions(&payload.diagram);
@@ -187,16 +203,8 @@ async fn new_git_diagram(
     *old_svg = svg.clone();
 
     // Retrieve the old svg
-    let (new_svgs, diffs) = {
-        match diff_from_strings(
-            &[start_svg, svg],
-            &Config {
-                matching: MatchingRules {
-                    rules,
-                    priorities: payload.priorities.clone(),
-                },
-            },
-        ) {
+    let (new_svgs, diffs, reports) = {
+        match diff_from_strings_with_reports(&[start_svg, svg], &config) {
             Ok(r) => r,
             Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
         }
@@ -205,6 +213,7 @@ async fn new_git_diagram(
     HttpResponse::Ok().json(ResultObject {
         svg: new_svgs[0].clone(),
         diffs: diffs[0].clone(),
+        report: reports[0].clone(),
     })
 }
 
@@ -236,6 +245,7 @@ async fn main() -> std::io::Result<()> {
             .service(new_pikchr_diagram)
             .service(new_git_diagram)
             .service(default_rules)
+            .service(default_priorities)
     })
     .bind(("127.0.0.1", 8080))?
     .run()
@@ -7,12 +7,13 @@ use actix_web::{get, web, App, HttpResponse, HttpServer, Result};
 use std::fs;
 use std::path::Path;
 use svg_diff::config::Config;
-use svg_diff::DiffStep;
+use svg_diff::{DiffStep, MatchingReport};
 
 #[derive(Clone)]
 struct AppState {
     base_svgs: Vec<String>,
     diffs: Vec<Vec<DiffStep>>,
+    reports: Vec<MatchingReport>,
 }
 
 // Function for finding the svg paths
@@ -68,6 +69,13 @@ async fn diffs(data: web::Data<AppState>) -> HttpResponse {
         .json(&data.diffs)
 }
 
+#[get("/reports.json")]
+async fn reports(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(&data.reports)
+}
+
 #[get("/js_assets/animator.js")]
 async fn animator_js() -> Result<NamedFile> {
     // Serve one of the possible pathes ...
@@ -96,12 +104,14 @@ async fn main() -> std::io::Result<()> {
         .collect();
 
     // Create the base and diff
-    let (base_svgs, svg_diffs) = svg_diff::diff_from_strings(&svgs, &Config::default()).unwrap();
+    let (base_svgs, svg_diffs, reports) =
+        svg_diff::diff_from_strings_with_reports(&svgs, &Config::default()).unwrap();
 
     // Create state
     let state = AppState {
         base_svgs,
         diffs: svg_diffs,
+        reports,
     };
 
     println!("Starting http server on http://127.0.0.1:8080");
@@ -111,6 +121,7 @@ async fn main() -> std::io::Result<()> {
             .service(root)
             .service(base_svg)
             .service(diffs)
+            .service(reports)
             .service(animator_js)
     })
     .bind(("127.0.0.1", 8080))?
@@ -1,4 +1,4 @@
-use crate::{diff_from_strings, DiffStep};
+use crate::{diff_from_strings_with_lifetimes, DiffStep};
 
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
@@ -8,27 +8,37 @@ use serde::{Deserialize, Serialize};
 struct JSResult {
     pub svgs: Vec<String>,
     pub diffs: Vec<Vec<serde_json::Value>>,
+    pub lifetimes: Vec<serde_json::Value>,
 }
 
 #[napi]
-fn svg_diffs(svg_strings: Vec<String>, config: Option<serde_json::Value>) -> JSResult {
+fn svg_diffs(
+    svg_strings: Vec<String>,
+    config: Option<serde_json::Value>,
+) -> napi::Result<JSResult> {
     // Read the config
     let default_config = crate::config::Config::default();
     let use_config = if let Some(c) = config {
-        serde_json::from_value(c).unwrap()
+        serde_json::from_value(c).map_err(|e| napi::Error::from_reason(e.to_string()))?
     } else {
         default_config
     };
 
     // Convert the svgs
-    let sdiff = diff_from_strings(&svg_strings, &use_config).unwrap();
+    let sdiff = diff_from_strings_with_lifetimes(&svg_strings, &use_config)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
 
-    JSResult {
+    Ok(JSResult {
         svgs: sdiff.0,
         diffs: sdiff
             .1
             .iter()
             .map(|v| v.iter().map(|v| serde_json::to_value(v).unwrap()).collect())
             .collect(),
-    }
+        lifetimes: sdiff
+            .2
+            .iter()
+            .map(|l| serde_json::to_value(l).unwrap())
+            .collect(),
+    })
 }
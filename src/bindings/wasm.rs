@@ -1,5 +1,5 @@
 use crate::config::Config;
-use crate::{diff_from_strings, DiffStep};
+use crate::{diff_from_strings_with_lifetimes, DiffStep, ElementLifetime};
 use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
@@ -7,6 +7,7 @@ use wasm_bindgen::prelude::*;
 struct JSResult {
     pub svgs: Vec<String>,
     pub diffs: Vec<Vec<DiffStep>>,
+    pub lifetimes: Vec<ElementLifetime>,
 }
 
 #[wasm_bindgen]
@@ -21,10 +22,11 @@ pub fn svg_diffs(svgs: JsValue, config: JsValue) -> Result<JsValue, JsValue> {
     };
 
     // Convert the svgs
-    let sdiff = diff_from_strings(&svgs, &use_config).map_err(|e| e.to_string())?;
+    let sdiff = diff_from_strings_with_lifetimes(&svgs, &use_config).map_err(|e| e.to_string())?;
 
     Ok(serde_wasm_bindgen::to_value(&JSResult {
         svgs: sdiff.0,
         diffs: sdiff.1,
+        lifetimes: sdiff.2,
     })?)
 }
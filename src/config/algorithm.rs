@@ -0,0 +1,171 @@
+use crate::errors::*;
+use serde::{Deserialize, Serialize};
+
+fn default_insert() -> f64 {
+    1.0
+}
+
+fn default_delete() -> f64 {
+    1.0
+}
+
+fn default_relabel() -> f64 {
+    1.0
+}
+
+fn default_attribute() -> f64 {
+    0.25
+}
+
+fn default_text() -> f64 {
+    0.5
+}
+
+fn default_min_height() -> usize {
+    1
+}
+
+fn default_min_dice() -> f64 {
+    0.5
+}
+
+/// The options of the GumTree matcher (see `Algorithm::GumTree`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GumTreeOptions {
+    /// Identical subtrees are only matched if they are at least this high
+    /// (a single element has the height 1).
+    #[serde(default = "default_min_height")]
+    pub min_height: usize,
+    /// Containers are matched if at least this ratio (between 0 and 1) of their
+    /// descendants are matched with each other.
+    #[serde(default = "default_min_dice")]
+    pub min_dice: f64,
+}
+
+impl Default for GumTreeOptions {
+    fn default() -> GumTreeOptions {
+        GumTreeOptions {
+            min_height: default_min_height(),
+            min_dice: default_min_dice(),
+        }
+    }
+}
+
+/// The costs of the operations of an edit script (see `Algorithm::TreeEditDistance`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EditCosts {
+    /// Adding an element (an `add` step).
+    #[serde(default = "default_insert")]
+    pub insert: f64,
+    /// Removing an element (a `remove` step).
+    #[serde(default = "default_delete")]
+    pub delete: f64,
+    /// Changing the tag name of an element. Only basic shapes can be relabeled
+    /// (a `change_shape` step), other elements are removed and added instead.
+    #[serde(default = "default_relabel")]
+    pub relabel: f64,
+    /// Every attribute that is added, removed or changed (in a `change` step).
+    #[serde(default = "default_attribute")]
+    pub attribute: f64,
+    /// Changing the text of an element (a `change_text` step).
+    #[serde(default = "default_text")]
+    pub text: f64,
+}
+
+impl Default for EditCosts {
+    fn default() -> EditCosts {
+        EditCosts {
+            insert: default_insert(),
+            delete: default_delete(),
+            relabel: default_relabel(),
+            attribute: default_attribute(),
+            text: default_text(),
+        }
+    }
+}
+
+/// How the elements of the SVGs are matched.
+///
+/// In the config it is given by its type, like
+/// `{type: tree_edit_distance, insert: 2, delete: 2}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Algorithm {
+    /// Match the children of matched elements by the hashes of the rules
+    /// in `MatchingRules::priorities` (see `MatchingRules`).
+    /// Fast, but greedy.
+    #[default]
+    HashPriority,
+    /// Match the elements by a minimum-cost edit script (Zhang-Shasha tree
+    /// edit distance) with the given costs. The matching rules are not used.
+    ///
+    /// The edit script is optimal, but takes quadratic memory and up to
+    /// quartic time in the number of elements, so it is meant for small SVGs.
+    TreeEditDistance(EditCosts),
+    /// Match identical subtrees anywhere in the SVGs (the biggest first), then
+    /// match the elements containing them by the ratio of matched descendants,
+    /// like GumTree does. Handles content that is regrouped (like a `g` added around it).
+    /// The matching rules are not used.
+    #[serde(rename = "gumtree")]
+    GumTree(GumTreeOptions),
+}
+
+impl Algorithm {
+    /// Checks that all costs are non-negative numbers and all ratios are between 0 and 1.
+    pub fn validate(&self) -> Result<()> {
+        if let Algorithm::GumTree(options) = self {
+            if !(0.0..=1.0).contains(&options.min_dice) {
+                bail!(ErrorKind::InvalidRatio(
+                    "min_dice".to_string(),
+                    options.min_dice
+                ));
+            }
+        }
+        if let Algorithm::TreeEditDistance(costs) = self {
+            for (name, cost) in [
+                ("insert", costs.insert),
+                ("delete", costs.delete),
+                ("relabel", costs.relabel),
+                ("attribute", costs.attribute),
+                ("text", costs.text),
+            ] {
+                if !cost.is_finite() || cost < 0.0 {
+                    bail!(ErrorKind::InvalidCost(name.to_string(), cost));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Algorithm, Config};
+
+    #[test]
+    fn parse_algorithm() {
+        let config: Config = serde_yaml::from_str(
+            "matching: {extends: pikchr}\nalgorithm: {type: tree_edit_distance, delete: 2}",
+        )
+        .unwrap();
+        match &config.algorithm {
+            Algorithm::TreeEditDistance(costs) => {
+                assert_eq!(costs.delete, 2.0);
+                assert_eq!(costs.insert, 1.0);
+            }
+            other => panic!("unexpected algorithm {:?}", other),
+        }
+        assert!(config.validate().is_ok());
+        let negative: Config = serde_yaml::from_str(
+            "matching: {extends: pikchr}\nalgorithm: {type: tree_edit_distance, text: -1}",
+        )
+        .unwrap();
+        assert!(negative.validate().is_err());
+        let gumtree: Config = serde_yaml::from_str(
+            "matching: {extends: pikchr}\nalgorithm: {type: gumtree, min_dice: 1.5}",
+        )
+        .unwrap();
+        assert!(matches!(gumtree.algorithm, Algorithm::GumTree(_)));
+        assert!(gumtree.validate().is_err());
+    }
+}
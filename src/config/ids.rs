@@ -0,0 +1,73 @@
+use crate::errors::*;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+
+/// How the ids of the matched elements are generated (see `MatchingIdGenerator`).
+///
+/// By default the ids get a random prefix, so every run gives other ids.
+/// With a `prefix`, a `seed` or `content_derived` ids the same inputs always
+/// give the same output.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct IdOptions {
+    /// The prefix of all ids (`<prefix>-<number>`) instead of a random one.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Generates the random prefix from this seed (if there is no `prefix`).
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Derives the ids from the content instead of numbering them: the tag name
+    /// and a hash of the positions of the matched elements, like `rect-3fa09c1b`.
+    #[serde(default)]
+    pub content_derived: bool,
+    /// The attribute the ids are written to, the diff steps address the elements by it.
+    /// With another attribute than `id` (like `data-svgdiff-id`) the `id` attributes
+    /// of the SVGs are kept, and diffed like any other attribute.
+    #[serde(default = "default_attribute")]
+    pub attribute: String,
+}
+
+fn default_attribute() -> String {
+    "id".to_string()
+}
+
+impl Default for IdOptions {
+    fn default() -> IdOptions {
+        IdOptions {
+            prefix: None,
+            seed: None,
+            content_derived: false,
+            attribute: default_attribute(),
+        }
+    }
+}
+
+impl IdOptions {
+    /// Whether the ids are written to the `id` attribute, so the elements
+    /// that already have an id keep it.
+    pub fn uses_id_attribute(&self) -> bool {
+        self.attribute == "id"
+    }
+
+    /// Checks that the prefix can be used in ids (letters, digits, `-` and `_`)
+    /// and that the attribute is a valid attribute name.
+    pub fn validate(&self) -> Result<()> {
+        if self.attribute.is_empty()
+            || !self
+                .attribute
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':')
+        {
+            bail!(ErrorKind::InvalidIdAttribute(self.attribute.clone()));
+        }
+        if let Some(prefix) = &self.prefix {
+            if prefix.is_empty()
+                || !prefix
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                bail!(ErrorKind::InvalidIdPrefix(prefix.clone()));
+            }
+        }
+        Ok(())
+    }
+}
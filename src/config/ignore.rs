@@ -0,0 +1,64 @@
+use crate::config::Selector;
+use crate::svg_data::Tag;
+use serde::{Deserialize, Serialize};
+
+/// Content that is left out of the diff, like generated timestamps.
+///
+/// Ignored content is removed before the SVGs are matched, so it never
+/// changes a hash or produces a `DiffStep`. It is still printed
+/// (unchanged) in the resulting SVGs.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Ignore {
+    /// Elements matching any of these selectors are ignored with all their descendants.
+    /// The root element is never ignored.
+    #[serde(default)]
+    pub elements: Vec<Selector>,
+    /// The names of the ignored attributes. A name ending with `*` ignores
+    /// all attributes starting with the rest of it, like `data-*`.
+    #[serde(default)]
+    pub attributes: Vec<String>,
+}
+
+impl Ignore {
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty() && self.attributes.is_empty()
+    }
+
+    /// Whether the element is ignored.
+    /// `ancestors` are the ancestors of the tag, starting with the root.
+    pub fn ignores_element(&self, tag: &Tag, ancestors: &[&Tag]) -> bool {
+        self.elements.iter().any(|s| s.matches(tag, ancestors))
+    }
+
+    pub fn ignores_attribute(&self, name: &str) -> bool {
+        self.attributes
+            .iter()
+            .any(|ignored| match ignored.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == ignored,
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::SVG;
+
+    #[test]
+    fn parse_ignore() {
+        let config: Config = serde_yaml::from_str(
+            "matching: {extends: pikchr}\nignore: {elements: [metadata, text.timestamp], attributes: [data-build, 'aria-*']}",
+        )
+        .unwrap();
+        let svg =
+            SVG::parse_svg_string(r#"<svg><text class="timestamp">now</text></svg>"#).unwrap();
+        let text = svg.tag(svg.child_positions(svg.root_position())[0]);
+        assert!(config
+            .ignore
+            .ignores_element(text, &[svg.tag(svg.root_position())]));
+        assert!(config.ignore.ignores_attribute("data-build"));
+        assert!(config.ignore.ignores_attribute("aria-label"));
+        assert!(!config.ignore.ignores_attribute("data-id"));
+    }
+}
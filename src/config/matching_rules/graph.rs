@@ -0,0 +1,62 @@
+use crate::config::Selector;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+fn default_nodes() -> Selector {
+    Selector::from_str("g.node").unwrap()
+}
+
+fn default_edges() -> Selector {
+    Selector::from_str("g.edge").unwrap()
+}
+
+fn default_title() -> String {
+    "title".to_string()
+}
+
+fn default_separators() -> Vec<String> {
+    vec!["->".to_string(), "--".to_string()]
+}
+
+/// Matches the nodes and edges of graph diagrams (like the output of Graphviz)
+/// by the names of the nodes, instead of by their place in the SVG.
+///
+/// Nodes with the same name are matched. Renamed nodes are matched with the
+/// node that has the most neighbours in common. Edges are matched by the
+/// nodes they connect, so they keep their ids when the layout changes.
+/// The matched groups are anchored (see `MatchingRules::key_attributes`).
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GraphMatching {
+    /// The groups that are nodes.
+    #[serde(default = "default_nodes")]
+    pub nodes: Selector,
+    /// The groups that are edges.
+    #[serde(default = "default_edges")]
+    pub edges: Selector,
+    /// The child element of a group, whose text is the name of the node or edge.
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// If set, the name of a node is the value of this attribute instead of its title.
+    #[serde(default)]
+    pub name_attribute: Option<String>,
+    /// If set, the nodes an edge connects are the values of these two
+    /// attributes, instead of being parsed from the title of the edge.
+    #[serde(default)]
+    pub endpoint_attributes: Option<(String, String)>,
+    /// The separators between the node names in the title of an edge (like `a->b`).
+    #[serde(default = "default_separators")]
+    pub separators: Vec<String>,
+}
+
+impl Default for GraphMatching {
+    fn default() -> GraphMatching {
+        GraphMatching {
+            nodes: default_nodes(),
+            edges: default_edges(),
+            title: default_title(),
+            name_attribute: None,
+            endpoint_attributes: None,
+            separators: default_separators(),
+        }
+    }
+}
@@ -1,12 +1,188 @@
+use crate::config::presets;
+use crate::errors::*;
+use crate::svg_data::GEOMETRY_ATTRIBUTES;
+use error_chain::bail;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
+mod graph;
+mod normalize;
 mod rule;
+mod scoring;
+mod validation;
+pub use graph::GraphMatching;
+pub use normalize::NormalizeOp;
+pub use normalize::Normalizer;
+pub use normalize::Pattern;
+pub use rule::AncestorRule;
+pub use rule::FuzzyText;
+pub use rule::GeometryMode;
+pub use rule::MatchingAttrRule;
 pub use rule::MatchingRule;
+pub use rule::ProximityRule;
+pub use rule::SiblingRule;
+pub use rule::TextMetric;
+pub use scoring::SimilarityScoring;
 
+/// The rules for matching elements.
+///
+/// When read from a config, the rules can extend a preset (see `presets`) with
+/// `extends: <name>`. Rules with the same name as in the preset replace them,
+/// other rules are added. All other settings replace those of the preset,
+/// only the `key_attributes` and `attribute_groups` are added.
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "MatchingRulesDef")]
 pub struct MatchingRules {
     pub rules: Vec<MatchingRule>,
     pub priorities: Vec<String>,
+    /// Named groups of attributes, that can be included or excluded by rules
+    /// as a whole. These are added to the built-in groups ("position", "geometry",
+    /// "paint", "typography" and "identity") and overwrite them if they have the same name.
+    #[serde(default)]
+    pub attribute_groups: HashMap<String, HashSet<String>>,
+    /// If set, children are matched by the best total similarity score
+    /// instead of by document order.
+    #[serde(default)]
+    pub scoring: Option<SimilarityScoring>,
+    /// Rules (by priority) for matching elements that could not be matched
+    /// under their parent with elements anywhere in the other SVG.
+    /// These elements are moved to their new parent.
+    /// Empty by default, so elements are only matched under their parent.
+    #[serde(default)]
+    pub cross_parent_priorities: Vec<String>,
+    /// Attributes that identify elements (like `data-key`).
+    /// Elements with the same value in a key attribute are always matched,
+    /// wherever they are in the SVGs. Keys must be unique in each SVG.
+    #[serde(default)]
+    pub key_attributes: Vec<String>,
+    /// If set, the nodes and edges of graph diagrams are matched by their names.
+    #[serde(default)]
+    pub graph: Option<GraphMatching>,
+}
+
+/// `MatchingRules` as they are written in a config.
+#[derive(Deserialize)]
+struct MatchingRulesDef {
+    extends: Option<String>,
+    #[serde(default)]
+    rules: Vec<MatchingRule>,
+    priorities: Option<Vec<String>>,
+    #[serde(default)]
+    attribute_groups: HashMap<String, HashSet<String>>,
+    scoring: Option<SimilarityScoring>,
+    cross_parent_priorities: Option<Vec<String>>,
+    #[serde(default)]
+    key_attributes: Vec<String>,
+    graph: Option<GraphMatching>,
+}
+
+impl TryFrom<MatchingRulesDef> for MatchingRules {
+    type Error = Error;
+
+    fn try_from(def: MatchingRulesDef) -> Result<MatchingRules> {
+        let mut res = match &def.extends {
+            Some(name) => presets::preset(name)?,
+            None => MatchingRules {
+                rules: Vec::new(),
+                priorities: Vec::new(),
+                attribute_groups: HashMap::new(),
+                scoring: None,
+                cross_parent_priorities: Vec::new(),
+                key_attributes: Vec::new(),
+                graph: None,
+            },
+        };
+        for rule in def.rules {
+            match res.rules.iter_mut().find(|r| r.name == rule.name) {
+                Some(existing) => *existing = rule,
+                None => res.rules.push(rule),
+            }
+        }
+        match def.priorities {
+            Some(priorities) => res.priorities = priorities,
+            None if def.extends.is_none() => bail!("missing field `priorities`"),
+            None => {}
+        }
+        res.attribute_groups.extend(def.attribute_groups);
+        if def.scoring.is_some() {
+            res.scoring = def.scoring;
+        }
+        if let Some(priorities) = def.cross_parent_priorities {
+            res.cross_parent_priorities = priorities;
+        }
+        if def.graph.is_some() {
+            res.graph = def.graph;
+        }
+        for key in def.key_attributes {
+            if !res.key_attributes.contains(&key) {
+                res.key_attributes.push(key);
+            }
+        }
+        Ok(res)
+    }
+}
+
+fn builtin_attribute_group(name: &str) -> Option<HashSet<String>> {
+    let attrs: &[&str] = match name {
+        "position" => &["x", "y", "cx", "cy"],
+        "geometry" => &GEOMETRY_ATTRIBUTES,
+        "paint" => &[
+            "fill",
+            "fill-opacity",
+            "stroke",
+            "stroke-opacity",
+            "stroke-width",
+            "stroke-dasharray",
+            "stroke-linecap",
+            "stroke-linejoin",
+            "opacity",
+            "color",
+        ],
+        "typography" => &[
+            "font-family",
+            "font-size",
+            "font-style",
+            "font-weight",
+            "letter-spacing",
+            "text-anchor",
+            "text-decoration",
+            "dominant-baseline",
+        ],
+        "identity" => &["id", "class"],
+        _ => return None,
+    };
+    Some(attrs.iter().map(|a| a.to_string()).collect())
+}
+
+impl MatchingRules {
+    /// The attributes in the group with the given name.
+    pub fn attribute_group(&self, name: &str) -> Option<HashSet<String>> {
+        self.attribute_groups
+            .get(name)
+            .cloned()
+            .or_else(|| builtin_attribute_group(name))
+    }
+
+    /// Whether the bounding boxes of the elements are needed for matching.
+    pub fn needs_bounding_boxes(&self) -> bool {
+        self.scoring.is_some() || self.rules.iter().any(|r| r.proximity.is_some())
+    }
+
+    /// The rules as they are used for hashing, i.E. with all the
+    /// attribute groups replaced by the attributes in them.
+    /// They are sorted in their evaluation order (see `evaluation_order`),
+    /// if the rules are valid.
+    pub fn resolved_rules(&self) -> Vec<MatchingRule> {
+        self.evaluation_order()
+            .unwrap_or_else(|_| self.rules.iter().collect())
+            .into_iter()
+            .map(|rule| {
+                let mut rule = rule.clone();
+                rule.resolve_attribute_groups(|name| self.attribute_group(name));
+                rule
+            })
+            .collect()
+    }
 }
 
 impl Default for MatchingRules {
@@ -14,13 +190,17 @@ impl Default for MatchingRules {
         MatchingRules {
             rules: MatchingRule::default_rules(),
             priorities: vec![
-                "next_is_same_text".to_string(),
                 "all".to_string(),
                 "with_reorder".to_string(),
                 "without_attr".to_string(),
                 "without_text".to_string(),
                 "only_tag".to_string(),
             ],
+            attribute_groups: HashMap::new(),
+            scoring: None,
+            cross_parent_priorities: Vec::new(),
+            key_attributes: Vec::new(),
+            graph: None,
         }
     }
 }
@@ -0,0 +1,128 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// A regular expression, (de)serialized as its pattern.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Pattern, regex::Error> {
+        Regex::new(&pattern).map(Pattern)
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(p: Pattern) -> String {
+        p.0.as_str().to_string()
+    }
+}
+
+/// How a value is normalized.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum NormalizeOp {
+    /// Replaces all matches of the pattern (`$1` ... refer to groups).
+    RegexReplace {
+        pattern: Pattern,
+        #[serde(default)]
+        replacement: String,
+    },
+    /// Removes whitespace at the start and the end.
+    Trim,
+    /// Replaces all whitespace by a single space (and trims).
+    CollapseWhitespace,
+    Lowercase,
+    /// Rounds all numbers to the given number of decimals.
+    RoundNumbers {
+        decimals: usize,
+    },
+}
+
+fn number_regex() -> &'static Regex {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    NUMBER.get_or_init(|| Regex::new(r"-?(\d+\.?\d*|\.\d+)([eE][-+]?\d+)?").unwrap())
+}
+
+/// Normalizes texts or attribute values before they are hashed by a rule,
+/// so that differences that don't matter (like generated suffixes of ids,
+/// whitespace or case) are ignored.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Normalizer {
+    /// The attributes (or properties in the `style` attribute) to normalize,
+    /// `*` stands for all attributes.
+    #[serde(default)]
+    pub attributes: HashSet<String>,
+    /// Whether the text of the elements is normalized.
+    #[serde(default)]
+    pub text: bool,
+    #[serde(flatten)]
+    pub op: NormalizeOp,
+}
+
+impl Normalizer {
+    pub fn applies_to_attr(&self, attr: &str) -> bool {
+        self.attributes.contains(attr) || self.attributes.contains("*")
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match &self.op {
+            NormalizeOp::RegexReplace {
+                pattern,
+                replacement,
+            } => pattern
+                .0
+                .replace_all(value, replacement.as_str())
+                .to_string(),
+            NormalizeOp::Trim => value.trim().to_string(),
+            NormalizeOp::CollapseWhitespace => {
+                value.split_whitespace().collect::<Vec<&str>>().join(" ")
+            }
+            NormalizeOp::Lowercase => value.to_lowercase(),
+            NormalizeOp::RoundNumbers { decimals } => number_regex()
+                .replace_all(value, |c: &regex::Captures| match c[0].parse::<f64>() {
+                    Ok(n) => format!("{:.*}", decimals, n),
+                    Err(_) => c[0].to_string(),
+                })
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn normalizer(op: &str) -> Normalizer {
+        serde_yaml::from_str(op).unwrap()
+    }
+
+    #[test]
+    fn ops() {
+        let suffix = normalizer("{op: regex_replace, pattern: '_[0-9a-f]{4}$', attributes: [id]}");
+        assert_eq!(suffix.apply("cluster_a83f"), "cluster");
+        assert!(suffix.applies_to_attr("id"));
+        assert!(!suffix.applies_to_attr("class"));
+        assert_eq!(
+            normalizer("{op: collapse_whitespace}").apply("  User \n Service "),
+            "User Service"
+        );
+        assert_eq!(
+            normalizer("{op: lowercase}").apply("UserService"),
+            "userservice"
+        );
+        assert_eq!(
+            normalizer("{op: round_numbers, decimals: 1}").apply("M 1.04,2.96 L-3 .5"),
+            "M 1.0,3.0 L-3.0 0.5"
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(serde_yaml::from_str::<Normalizer>("{op: regex_replace, pattern: '('}").is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use crate::svg_data::Tag;
+use super::normalize::Normalizer;
+use crate::config::Selector;
+use crate::svg_data::{is_basic_shape, BoundingBox, Tag, GEOMETRY_ATTRIBUTES, SHAPE_ATTRIBUTES};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -6,14 +8,105 @@ fn default_true() -> bool {
     true
 }
 
+/// How the geometry (position and size) of an element is compared.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GeometryMode {
+    /// The geometric attributes are compared like any other attribute.
+    #[default]
+    Exact,
+    /// Shapes are compared independent of where they are placed.
+    Translation,
+    /// Like `Translation`, but shapes are also compared independent of their size.
+    TranslationAndScale,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct MatchingAttrRule {
     pub included_attr: Option<HashSet<String>>,
     pub exclude_attr: HashSet<String>,
+    /// Names of attribute groups (see `MatchingRules::attribute_groups`) to include.
+    #[serde(default)]
+    pub include_groups: HashSet<String>,
+    /// Names of attribute groups to exclude.
+    #[serde(default)]
+    pub exclude_groups: HashSet<String>,
+    /// If false, `x`, `y`, `cx` and `cy` are excluded.
     #[serde(default = "default_true")]
     pub with_pos: bool,
+    /// If false, `fill` and `stroke` are excluded.
     #[serde(default = "default_true")]
     pub with_style: bool,
+    #[serde(default)]
+    pub geometry: GeometryMode,
+}
+
+/// Restricts matches to elements that are at about the same place.
+///
+/// The bounding boxes of the elements (including their children) must either
+/// have centers not further apart than `max_distance` or overlap by at least
+/// `min_overlap` (intersection over union, between 0 and 1).
+/// Of several candidates the closest are matched.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProximityRule {
+    pub max_distance: Option<f64>,
+    pub min_overlap: Option<f64>,
+}
+
+impl ProximityRule {
+    /// Returns the distance between the boxes, if they are close enough.
+    pub(crate) fn distance(&self, a: &BoundingBox, b: &BoundingBox) -> Option<f64> {
+        let distance = a.center_distance(b);
+        let near = self.max_distance.map(|d| distance <= d).unwrap_or(false);
+        let overlaps = self.min_overlap.map(|o| a.overlap(b) >= o).unwrap_or(false);
+        if near || overlaps {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the similarity of two texts is measured.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextMetric {
+    /// The edit distance normalized by the length of the longer text.
+    #[default]
+    Levenshtein,
+    /// The ratio of words that are in both texts.
+    TokenOverlap,
+}
+
+/// Compares texts by their similarity instead of by equality.
+///
+/// The text is not part of the hash of the rule. Instead elements (and siblings
+/// matched by this rule, see `MatchingRule::prev_sibling_rule`) only match if the
+/// similarity of their texts (including the texts of their children) is at least
+/// `min_similarity` (between 0 and 1). Of several candidates the most similar
+/// are matched.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FuzzyText {
+    pub min_similarity: f64,
+    #[serde(default)]
+    pub metric: TextMetric,
+}
+
+/// Refers to the nearest ancestor of an element that matches the selector.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AncestorRule {
+    pub selector: Selector,
+    /// The rule by which the ancestor is hashed.
+    pub rule: String,
+}
+
+/// Refers to another child of the same parent.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SiblingRule {
+    /// The position relative to the element, `-2` is the sibling two before it.
+    pub offset: isize,
+    /// The rule by which the sibling is hashed.
+    pub rule: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -22,6 +115,10 @@ pub struct MatchingRule {
     pub apply_to_tags: Option<HashSet<String>>,
     #[serde(default = "HashSet::new")]
     pub dont_apply_to_tags: HashSet<String>,
+    /// If set, the rule only applies to elements matching this (CSS) selector,
+    /// like `g.node > text` or `[data-type=edge]`.
+    #[serde(default)]
+    pub apply_to: Option<Selector>,
     pub attr: Option<MatchingAttrRule>,
     #[serde(default = "default_true")]
     pub include_text: bool,
@@ -32,140 +129,128 @@ pub struct MatchingRule {
     pub sort_children: bool,
     pub prev_sibling_rule: Option<String>,
     pub next_sibling_rule: Option<String>,
+    /// Like the sibling rules, but for the parent: the rule only applies to
+    /// elements whose parent has a hash by this rule.
+    #[serde(default)]
+    pub parent_rule: Option<String>,
+    /// Like `parent_rule`, but for the nearest ancestor matching a selector
+    /// (like the group of a node, that is identified by its title).
+    #[serde(default)]
+    pub ancestor: Option<AncestorRule>,
+    /// Like the sibling rules, but for a sibling further away.
+    #[serde(default)]
+    pub sibling: Option<SiblingRule>,
+    /// If set, elements are only matched if they are close to each other.
+    #[serde(default)]
+    pub proximity: Option<ProximityRule>,
+    /// If set, the text is compared by similarity (`include_text` is ignored).
+    #[serde(default)]
+    pub fuzzy_text: Option<FuzzyText>,
+    /// Normalizers applied (in order) to texts and attribute values before hashing.
+    #[serde(default)]
+    pub normalize: Vec<Normalizer>,
+    /// If true, basic shapes (rect, circle, ellipse, line, polyline, polygon
+    /// and path) are compared by their outline, drawn as a path, instead of
+    /// by their tag name and attributes. So a rect and a path drawing the same
+    /// rectangle get the same hash. The outline is only compared if the
+    /// geometry is included (see `MatchingAttrRule::geometry`).
+    #[serde(default)]
+    pub canonical_shapes: bool,
 }
 
-impl MatchingRule {
-    pub fn new_all_rule() -> MatchingRule {
+impl Default for MatchingAttrRule {
+    /// All attributes, like a rule with no attribute settings in a config.
+    fn default() -> MatchingAttrRule {
+        MatchingAttrRule {
+            included_attr: None,
+            exclude_attr: HashSet::new(),
+            include_groups: HashSet::new(),
+            exclude_groups: HashSet::new(),
+            with_pos: true,
+            with_style: true,
+            geometry: GeometryMode::Exact,
+        }
+    }
+}
+
+impl Default for MatchingRule {
+    /// A rule without a name and without attributes, with the same
+    /// defaults as a rule in a config.
+    fn default() -> MatchingRule {
         MatchingRule {
-            name: "all".to_string(),
+            name: String::new(),
             apply_to_tags: None,
             dont_apply_to_tags: HashSet::new(),
-            attr: Some(MatchingAttrRule {
-                included_attr: None,
-                exclude_attr: HashSet::new(),
-                with_pos: true,
-                with_style: true,
-            }),
+            apply_to: None,
+            attr: None,
             include_text: true,
             recursive: true,
             childrens_rule: None,
-            sort_children: false,
+            sort_children: true,
             prev_sibling_rule: None,
             next_sibling_rule: None,
+            parent_rule: None,
+            ancestor: None,
+            sibling: None,
+            proximity: None,
+            fuzzy_text: None,
+            normalize: Vec::new(),
+            canonical_shapes: false,
+        }
+    }
+}
+
+impl MatchingRule {
+    pub fn new_all_rule() -> MatchingRule {
+        MatchingRule {
+            name: "all".to_string(),
+            attr: Some(MatchingAttrRule::default()),
+            sort_children: false,
+            ..Default::default()
         }
     }
 
     pub fn new_all_without_subtrees_rule() -> MatchingRule {
         MatchingRule {
             name: "all".to_string(),
-            apply_to_tags: None,
-            dont_apply_to_tags: HashSet::new(),
-            attr: Some(MatchingAttrRule {
-                included_attr: None,
-                exclude_attr: HashSet::new(),
-                with_pos: true,
-                with_style: true,
-            }),
-            include_text: true,
+            attr: Some(MatchingAttrRule::default()),
             recursive: false,
-            childrens_rule: None,
             sort_children: false,
-            prev_sibling_rule: None,
-            next_sibling_rule: None,
+            ..Default::default()
         }
     }
 
     pub fn new_all_subtrees_rule() -> MatchingRule {
         MatchingRule {
             name: "all_children".to_string(),
-            apply_to_tags: None,
-            dont_apply_to_tags: HashSet::new(),
-            attr: None,
             include_text: false,
-            recursive: true,
             childrens_rule: Some("all".to_string()),
             sort_children: false,
-            prev_sibling_rule: None,
-            next_sibling_rule: None,
+            ..Default::default()
         }
     }
 
     pub fn default_rules() -> Vec<MatchingRule> {
         vec![
-            MatchingRule {
-                name: "next_is_same_text".to_string(),
-                apply_to_tags: None,
-                dont_apply_to_tags: Default::default(),
-                attr: Some(MatchingAttrRule {
-                    included_attr: Some(HashSet::new()),
-                    exclude_attr: HashSet::new(),
-                    with_pos: true,
-                    with_style: true,
-                }),
-                include_text: true,
-                recursive: false,
-                childrens_rule: None,
-                sort_children: false,
-                prev_sibling_rule: None,
-                next_sibling_rule: Some("same_text_in_text".to_string()),
-            },
             MatchingRule {
                 name: "with_reorder".to_string(),
-                apply_to_tags: None,
-                dont_apply_to_tags: HashSet::new(),
-                attr: Some(MatchingAttrRule {
-                    included_attr: None,
-                    exclude_attr: HashSet::new(),
-                    with_pos: true,
-                    with_style: true,
-                }),
-                include_text: true,
-                recursive: true,
-                childrens_rule: None,
-                sort_children: true,
-                prev_sibling_rule: None,
-                next_sibling_rule: None,
+                attr: Some(MatchingAttrRule::default()),
+                ..Default::default()
             },
             MatchingRule {
                 name: "without_attr".to_string(),
-                apply_to_tags: None,
-                dont_apply_to_tags: HashSet::new(),
-                attr: None,
-                include_text: true,
-                recursive: true,
-                childrens_rule: None,
-                sort_children: true,
-                prev_sibling_rule: None,
-                next_sibling_rule: None,
+                ..Default::default()
             },
             MatchingRule {
                 name: "without_text".to_string(),
-                apply_to_tags: None,
-                dont_apply_to_tags: HashSet::new(),
-                attr: Some(MatchingAttrRule {
-                    included_attr: None,
-                    exclude_attr: HashSet::new(),
-                    with_pos: true,
-                    with_style: true,
-                }),
+                attr: Some(MatchingAttrRule::default()),
                 include_text: false,
-                recursive: true,
-                childrens_rule: None,
-                sort_children: true,
-                prev_sibling_rule: None,
-                next_sibling_rule: None,
+                ..Default::default()
             },
             MatchingRule {
                 name: "only_tag".to_string(),
-                apply_to_tags: None,
-                dont_apply_to_tags: HashSet::new(),
-                attr: None,
                 include_text: false,
-                recursive: true,
-                childrens_rule: None,
-                sort_children: true,
-                prev_sibling_rule: None,
-                next_sibling_rule: None,
+                ..Default::default()
             },
         ]
     }
@@ -179,6 +264,41 @@ impl MatchingRule {
         !self.dont_apply_to_tags.contains(&tag.name.to_string())
     }
 
+    /// The text, normalized by the normalizers of this rule.
+    pub fn normalize_text(&self, text: &str) -> String {
+        self.normalize
+            .iter()
+            .filter(|n| n.text)
+            .fold(text.to_string(), |text, n| n.apply(&text))
+    }
+
+    /// The value of the attribute (or style property) normalized by the
+    /// normalizers of this rule, `None` if no normalizer applies to it.
+    pub fn normalize_attr(&self, attr: &str, value: &str) -> Option<String> {
+        let mut normalizers = self
+            .normalize
+            .iter()
+            .filter(|n| n.applies_to_attr(attr))
+            .peekable();
+        normalizers.peek()?;
+        Some(normalizers.fold(value.to_string(), |value, n| n.apply(&value)))
+    }
+
+    /// Whether the hash needs the ancestors of an element.
+    pub(crate) fn needs_ancestors(&self) -> bool {
+        self.apply_to.is_some() || self.parent_rule.is_some() || self.ancestor.is_some()
+    }
+
+    /// Whether the rule applies to the tag, which has the given `ancestors` (root first).
+    pub fn applies_to(&self, tag: &Tag, ancestors: &[&Tag]) -> bool {
+        self.applies_to_tag(tag)
+            && self
+                .apply_to
+                .as_ref()
+                .map(|s| s.matches(tag, ancestors))
+                .unwrap_or(true)
+    }
+
     fn attr_is_excluded(&self, attr: &str) -> bool {
         if let Some(attr_opt) = self.attr.as_ref() {
             if attr_opt.exclude_attr.contains(attr) {
@@ -190,30 +310,114 @@ impl MatchingRule {
             if !attr_opt.with_style && ["fill", "stroke"].contains(&attr) {
                 return true;
             }
+            // The geometry is hashed as a whole (see `geometry_mode`)
+            if attr_opt.geometry != GeometryMode::Exact && GEOMETRY_ATTRIBUTES.contains(&attr) {
+                return true;
+            }
             false
         } else {
             true
         }
     }
 
-    pub fn included_sorted_attr<'a>(&self, tag: &'a Tag) -> Vec<&'a String> {
-        let mut res = Vec::new();
+    /// The geometry mode, if the geometry is compared as a normalized shape
+    /// instead of by its attributes.
+    pub fn geometry_mode(&self) -> Option<GeometryMode> {
+        self.attr
+            .as_ref()
+            .map(|a| a.geometry)
+            .filter(|g| *g != GeometryMode::Exact)
+    }
+
+    /// Whether the tag is compared by its outline (see `canonical_shapes`).
+    pub(crate) fn uses_canonical_shape(&self, tag: &Tag) -> bool {
+        self.canonical_shapes && is_basic_shape(&tag.name)
+    }
+
+    /// How the outline of basic shapes is compared, `None` if the geometry is not included.
+    pub(crate) fn canonical_shape_mode(&self) -> Option<GeometryMode> {
+        self.geometry_mode().or_else(|| {
+            if !self.attr_is_included("d") {
+                None
+            } else if self.attr.as_ref().map(|a| a.with_pos).unwrap_or(false) {
+                Some(GeometryMode::Exact)
+            } else {
+                Some(GeometryMode::Translation)
+            }
+        })
+    }
+
+    fn attr_is_included(&self, attr: &str) -> bool {
         if let Some(attr_opt) = &self.attr {
             if let Some(inc_attr) = &attr_opt.included_attr {
-                for attr in tag.args.keys() {
-                    if inc_attr.contains(attr) && !self.attr_is_excluded(attr) {
-                        res.push(attr);
-                    }
-                }
-            } else {
-                for attr in tag.args.keys() {
-                    if !self.attr_is_excluded(attr) {
-                        res.push(attr);
-                    }
+                if !inc_attr.contains(attr) {
+                    return false;
                 }
             }
-            res.sort();
+            !self.attr_is_excluded(attr)
+        } else {
+            false
+        }
+    }
+
+    /// Properties in the `style` attribute are handled like attributes of their own.
+    /// They are included, if they are included themselves or if the whole
+    /// style attribute is included (and they are not excluded).
+    pub fn style_property_is_included(&self, prop: &str) -> bool {
+        if self.attr_is_excluded("style") {
+            return false;
         }
+        self.attr_is_included(prop)
+            || (self.attr_is_included("style") && !self.attr_is_excluded(prop))
+    }
+
+    pub fn included_sorted_attr<'a>(&self, tag: &'a Tag) -> Vec<&'a String> {
+        let mut res = Vec::new();
+        for (attr, value) in tag.args.iter() {
+            let included =
+                if self.uses_canonical_shape(tag) && SHAPE_ATTRIBUTES.contains(&attr.as_str()) {
+                    // The outline is hashed as a whole (see `canonical_shapes`)
+                    false
+                } else if attr == "style" {
+                    value
+                        .style_properties()
+                        .iter()
+                        .any(|(prop, _)| self.style_property_is_included(prop))
+                } else {
+                    self.attr_is_included(attr)
+                };
+            if included {
+                res.push(attr);
+            }
+        }
+        res.sort();
         res
     }
+
+    /// Replaces the attribute groups of this rule with the attributes in these groups.
+    pub(crate) fn resolve_attribute_groups<F>(&mut self, group: F)
+    where
+        F: Fn(&str) -> Option<HashSet<String>>,
+    {
+        if let Some(attr_opt) = self.attr.as_mut() {
+            let resolve = |name: &str| {
+                group(name).unwrap_or_else(|| {
+                    log::warn!("rule {} uses unknown attribute group {}", self.name, name);
+                    HashSet::new()
+                })
+            };
+            if !attr_opt.include_groups.is_empty() {
+                let mut included = attr_opt.included_attr.take().unwrap_or_default();
+                for name in attr_opt.include_groups.iter() {
+                    included.extend(resolve(name));
+                }
+                attr_opt.included_attr = Some(included);
+            }
+            for name in attr_opt.exclude_groups.iter() {
+                attr_opt.exclude_attr.extend(resolve(name));
+            }
+            attr_opt.include_groups.clear();
+            attr_opt.exclude_groups.clear();
+        }
+    }
 }
@@ -0,0 +1,86 @@
+use crate::errors::*;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+
+fn default_min_score() -> f64 {
+    0.5
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_distance_scale() -> f64 {
+    100.0
+}
+
+/// Scoring of candidate pairs for the optimal matching of children.
+///
+/// If set, the children of a matched parent are not matched in document order.
+/// Instead all pairs that match by a rule are scored by their similarity and
+/// the assignment with the best total score is chosen.
+/// The score is the weighted average of:
+///
+/// * The ratio of attributes that are equal in both elements.
+/// * The similarity of the text (normalized edit distance).
+/// * The distance of the bounding boxes, `1 / (1 + distance / distance_scale)`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SimilarityScoring {
+    /// Pairs with a lower score are not matched (and are added/removed instead).
+    #[serde(default = "default_min_score")]
+    pub min_score: f64,
+    #[serde(default = "default_weight")]
+    pub attr_weight: f64,
+    #[serde(default = "default_weight")]
+    pub text_weight: f64,
+    #[serde(default = "default_weight")]
+    pub distance_weight: f64,
+    /// The distance at which the distance similarity drops to 0.5.
+    #[serde(default = "default_distance_scale")]
+    pub distance_scale: f64,
+}
+
+impl Default for SimilarityScoring {
+    fn default() -> SimilarityScoring {
+        SimilarityScoring {
+            min_score: default_min_score(),
+            attr_weight: default_weight(),
+            text_weight: default_weight(),
+            distance_weight: default_weight(),
+            distance_scale: default_distance_scale(),
+        }
+    }
+}
+
+impl SimilarityScoring {
+    /// Checks that the scores are between 0 and 1.
+    ///
+    /// # Errors
+    ///
+    /// If `min_score` is not between 0 and 1, a weight is negative
+    /// or `distance_scale` is not positive.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.min_score) {
+            bail!(ErrorKind::InvalidRatio(
+                "min_score".to_string(),
+                self.min_score
+            ));
+        }
+        for (name, weight) in [
+            ("attr_weight", self.attr_weight),
+            ("text_weight", self.text_weight),
+            ("distance_weight", self.distance_weight),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                bail!(ErrorKind::InvalidWeight(name.to_string(), weight));
+            }
+        }
+        if !self.distance_scale.is_finite() || self.distance_scale <= 0.0 {
+            bail!(ErrorKind::InvalidScale(
+                "distance_scale".to_string(),
+                self.distance_scale
+            ));
+        }
+        Ok(())
+    }
+}
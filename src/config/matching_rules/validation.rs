@@ -0,0 +1,365 @@
+use super::{MatchingRule, MatchingRules};
+use crate::errors::*;
+use crate::SVG;
+use error_chain::bail;
+use std::collections::{HashMap, HashSet};
+
+/// The rules that are always available (see `TreeHash`).
+const BUILTIN_RULES: [&str; 3] = ["all", "all_subtrees", "all_without_subtrees"];
+
+/// The rules a rule needs to be computed first, with the kind of reference.
+fn references(rule: &MatchingRule) -> Vec<(&'static str, &String)> {
+    let mut res = Vec::new();
+    if let Some(r) = &rule.childrens_rule {
+        res.push(("childrens_rule", r));
+    }
+    if let Some(r) = &rule.prev_sibling_rule {
+        res.push(("prev_sibling_rule", r));
+    }
+    if let Some(r) = &rule.next_sibling_rule {
+        res.push(("next_sibling_rule", r));
+    }
+    if let Some(r) = &rule.parent_rule {
+        res.push(("parent_rule", r));
+    }
+    if let Some(a) = &rule.ancestor {
+        res.push(("ancestor", &a.rule));
+    }
+    if let Some(s) = &rule.sibling {
+        res.push(("sibling", &s.rule));
+    }
+    res
+}
+
+/// Whether the hash of `rule` needs the hash of `reference` computed in a pass before.
+/// Children and previous siblings are visited before the element itself,
+/// so a rule can reference itself for them (but not for next siblings or ancestors).
+fn needs_before(rule: &MatchingRule, kind: &str, reference: &str) -> bool {
+    if reference != rule.name {
+        return true;
+    }
+    match kind {
+        "childrens_rule" | "prev_sibling_rule" => false,
+        "sibling" => rule.sibling.as_ref().map(|s| s.offset >= 0).unwrap_or(true),
+        _ => true,
+    }
+}
+
+impl MatchingRules {
+    /// Checks that all rules and attribute groups that are referenced exist,
+    /// that rule names are unique, that the rules don't depend on each other
+    /// in a cycle and that the scoring is valid.
+    /// Rules that can never apply are logged as warnings.
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for rule in &self.rules {
+            if BUILTIN_RULES.contains(&rule.name.as_str()) || !names.insert(rule.name.as_str()) {
+                bail!(ErrorKind::DuplicateRule(rule.name.clone()));
+            }
+        }
+        let known = |name: &str| names.contains(name) || BUILTIN_RULES.contains(&name);
+        for rule in &self.rules {
+            for (kind, reference) in references(rule) {
+                if !known(reference) {
+                    bail!(ErrorKind::UnknownRule(
+                        reference.clone(),
+                        format!("{} of rule {}", kind, rule.name)
+                    ));
+                }
+            }
+            if let Some(attr) = &rule.attr {
+                for name in attr.include_groups.iter().chain(attr.exclude_groups.iter()) {
+                    if self.attribute_group(name).is_none() {
+                        bail!(ErrorKind::UnknownAttributeGroup(
+                            name.clone(),
+                            rule.name.clone()
+                        ));
+                    }
+                }
+            }
+        }
+        for (list, priorities) in [
+            ("priorities", &self.priorities),
+            ("cross_parent_priorities", &self.cross_parent_priorities),
+        ] {
+            for name in priorities {
+                if !known(name) {
+                    bail!(ErrorKind::UnknownRule(name.clone(), list.to_string()));
+                }
+            }
+        }
+        self.evaluation_order()?;
+        if let Some(scoring) = &self.scoring {
+            scoring.validate()?;
+        }
+        for warning in self.warnings() {
+            log::warn!("{}", warning);
+        }
+        Ok(())
+    }
+
+    /// Checks that every key (see `key_attributes`) is used by at most one element of the SVG.
+    pub fn validate_keys(&self, svg: &SVG) -> Result<()> {
+        if !self.key_attributes.is_empty() {
+            svg.elements_by_key(&self.key_attributes)?;
+        }
+        Ok(())
+    }
+
+    /// The rules in the order their hashes have to be computed,
+    /// so that every rule comes after the rules it references.
+    ///
+    /// # Errors
+    ///
+    /// If rules reference each other in a cycle.
+    pub fn evaluation_order(&self) -> Result<Vec<&MatchingRule>> {
+        let by_name: HashMap<&str, &MatchingRule> =
+            self.rules.iter().map(|r| (r.name.as_str(), r)).collect();
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        for rule in &self.rules {
+            let mut path = Vec::new();
+            visit(rule, &by_name, &mut path, &mut done, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Descriptions of rules that can never apply.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        let used: HashSet<&String> = self
+            .priorities
+            .iter()
+            .chain(self.cross_parent_priorities.iter())
+            .chain(
+                self.rules
+                    .iter()
+                    .flat_map(|r| references(r).into_iter().map(|r| r.1)),
+            )
+            .collect();
+        for rule in &self.rules {
+            if !used.contains(&rule.name) {
+                res.push(format!("rule {} is not used", rule.name));
+            }
+            if let Some(tags) = &rule.apply_to_tags {
+                if tags.iter().all(|t| rule.dont_apply_to_tags.contains(t)) {
+                    res.push(format!("rule {} applies to no tags", rule.name));
+                }
+            }
+            if let Some(proximity) = &rule.proximity {
+                if proximity.max_distance.is_none() && proximity.min_overlap.is_none() {
+                    res.push(format!(
+                        "rule {} has neither a max_distance nor a min_overlap",
+                        rule.name
+                    ));
+                }
+            }
+            if rule
+                .sibling
+                .as_ref()
+                .map(|s| s.offset == 0)
+                .unwrap_or(false)
+            {
+                res.push(format!(
+                    "rule {} refers to a sibling with offset 0, the element itself",
+                    rule.name
+                ));
+            }
+            if let Some(fuzzy) = &rule.fuzzy_text {
+                if fuzzy.min_similarity > 1.0 {
+                    res.push(format!(
+                        "rule {} has a min_similarity above 1 and never matches",
+                        rule.name
+                    ));
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Depth first search, adding the rule to `order` after all rules it depends on.
+fn visit<'a>(
+    rule: &'a MatchingRule,
+    by_name: &HashMap<&str, &'a MatchingRule>,
+    path: &mut Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+    order: &mut Vec<&'a MatchingRule>,
+) -> Result<()> {
+    if done.contains(rule.name.as_str()) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|n| *n == rule.name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(&rule.name);
+        bail!(ErrorKind::RuleCycle(cycle.join(" -> ")));
+    }
+    path.push(&rule.name);
+    for (kind, reference) in references(rule) {
+        if !needs_before(rule, kind, reference) {
+            continue;
+        }
+        if reference == &rule.name {
+            // Next siblings and ancestors, which are not computed yet
+            bail!(ErrorKind::RuleCycle(format!("{0} -> {0}", rule.name)));
+        }
+        if let Some(other) = by_name.get(reference.as_str()) {
+            visit(other, by_name, path, done, order)?;
+        }
+    }
+    path.pop();
+    done.insert(&rule.name);
+    order.push(rule);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{SiblingRule, SimilarityScoring};
+
+    fn rule(name: &str) -> MatchingRule {
+        let mut rule = MatchingRule::new_all_rule();
+        rule.name = name.to_string();
+        rule
+    }
+
+    fn rules(rules: Vec<MatchingRule>) -> MatchingRules {
+        MatchingRules {
+            priorities: rules.iter().map(|r| r.name.clone()).collect(),
+            rules,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_rules_are_valid() {
+        MatchingRules::default().validate().unwrap();
+    }
+
+    #[test]
+    fn custom_rules_only() {
+        let rules: MatchingRules = serde_yaml::from_str(
+            r#"
+            rules:
+              - name: same_id
+                attr: {included_attr: [id], exclude_attr: []}
+            priorities: [same_id, all]
+            "#,
+        )
+        .unwrap();
+        assert!(rules.cross_parent_priorities.is_empty());
+        rules.validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_rules() {
+        let mut parent = rule("parent");
+        parent.childrens_rule = Some("child".to_string());
+        assert!(rules(vec![parent.clone()]).validate().is_err());
+        assert!(rules(vec![parent, rule("child")]).validate().is_ok());
+
+        let mut unknown_priority = rules(vec![rule("a")]);
+        unknown_priority.priorities.push("b".to_string());
+        assert!(unknown_priority.validate().is_err());
+    }
+
+    #[test]
+    fn unknown_attribute_groups() {
+        let mut with_groups = rule("a");
+        let attr = with_groups.attr.as_mut().unwrap();
+        attr.include_groups.insert("position".to_string());
+        attr.exclude_groups.insert("style".to_string());
+        let mut rules = rules(vec![with_groups]);
+        rules
+            .attribute_groups
+            .insert("style".to_string(), HashSet::new());
+        rules.validate().unwrap();
+        rules.rules[0]
+            .attr
+            .as_mut()
+            .unwrap()
+            .exclude_groups
+            .insert("unknown".to_string());
+        let error = rules.validate().unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UnknownAttributeGroup(..)));
+    }
+
+    #[test]
+    fn invalid_scoring() {
+        let mut rules = MatchingRules::default();
+        rules.scoring = Some(SimilarityScoring::default());
+        rules.validate().unwrap();
+        for invalid in [
+            SimilarityScoring {
+                min_score: 1.5,
+                ..Default::default()
+            },
+            SimilarityScoring {
+                text_weight: -1.0,
+                ..Default::default()
+            },
+            SimilarityScoring {
+                distance_scale: 0.0,
+                ..Default::default()
+            },
+        ] {
+            rules.scoring = Some(invalid);
+            assert!(rules.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let svg =
+            SVG::parse_svg_string(r#"<svg><rect data-key="a"/><g data-key="a"/></svg>"#).unwrap();
+        let mut rules = MatchingRules::default();
+        rules.validate_keys(&svg).unwrap();
+        rules.key_attributes = vec!["data-key".to_string()];
+        assert!(rules.validate_keys(&svg).is_err());
+    }
+
+    #[test]
+    fn evaluation_order() {
+        let mut a = rule("a");
+        a.next_sibling_rule = Some("b".to_string());
+        a.prev_sibling_rule = Some("a".to_string());
+        let mut b = rule("b");
+        b.childrens_rule = Some("c".to_string());
+        let c = rule("c");
+        let rules = rules(vec![a, b, c]);
+        let order: Vec<&str> = rules
+            .evaluation_order()
+            .unwrap()
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(order, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn cycles() {
+        let mut a = rule("a");
+        a.next_sibling_rule = Some("b".to_string());
+        let mut b = rule("b");
+        b.prev_sibling_rule = Some("a".to_string());
+        assert!(rules(vec![a, b]).validate().is_err());
+
+        let mut own_next = rule("a");
+        own_next.next_sibling_rule = Some("a".to_string());
+        assert!(rules(vec![own_next]).validate().is_err());
+
+        let mut own_parent = rule("a");
+        own_parent.parent_rule = Some("a".to_string());
+        assert!(rules(vec![own_parent]).validate().is_err());
+
+        let mut own_sibling = rule("a");
+        own_sibling.sibling = Some(SiblingRule {
+            offset: -2,
+            rule: "a".to_string(),
+        });
+        assert!(rules(vec![own_sibling.clone()]).validate().is_ok());
+        own_sibling.sibling.as_mut().unwrap().offset = 2;
+        assert!(rules(vec![own_sibling]).validate().is_err());
+    }
+}
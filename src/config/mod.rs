@@ -1,11 +1,51 @@
+use crate::errors::*;
 use serde::{Deserialize, Serialize};
 
+mod algorithm;
+mod ids;
+mod ignore;
 mod matching_rules;
+pub mod presets;
+mod selector;
+pub use algorithm::Algorithm;
+pub use algorithm::EditCosts;
+pub use algorithm::GumTreeOptions;
+pub use ids::IdOptions;
+pub use ignore::Ignore;
+pub use matching_rules::AncestorRule;
+pub use matching_rules::FuzzyText;
+pub use matching_rules::GeometryMode;
+pub use matching_rules::GraphMatching;
+pub use matching_rules::MatchingAttrRule;
 pub use matching_rules::MatchingRule;
 pub use matching_rules::MatchingRules;
+pub use matching_rules::NormalizeOp;
+pub use matching_rules::Normalizer;
+pub use matching_rules::Pattern;
+pub use matching_rules::ProximityRule;
+pub use matching_rules::SiblingRule;
+pub use matching_rules::SimilarityScoring;
+pub use matching_rules::TextMetric;
+pub use selector::Selector;
 
 // Get all and all subtrees hashes
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
 pub struct Config {
     pub matching: MatchingRules,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub ignore: Ignore,
+    #[serde(default)]
+    pub ids: IdOptions,
+}
+
+impl Config {
+    /// Checks the config, see `MatchingRules::validate`, `Algorithm::validate`
+    /// and `IdOptions::validate`.
+    pub fn validate(&self) -> Result<()> {
+        self.matching.validate()?;
+        self.algorithm.validate()?;
+        self.ids.validate()
+    }
 }
@@ -0,0 +1,75 @@
+//! Named presets of matching rules, tuned for the output of common diagram generators.
+//!
+//! A config can extend a preset (`extends: graphviz`) and override single rules
+//! (by name), the priorities or any other setting.
+
+use crate::config::MatchingRules;
+use crate::errors::*;
+use error_chain::bail;
+
+/// The names of all presets.
+pub const PRESETS: [&str; 6] = [
+    "default", "pikchr", "graphviz", "plantuml", "mermaid", "drawio",
+];
+
+/// The preset with the given name.
+pub fn preset(name: &str) -> Result<MatchingRules> {
+    let yaml = match name {
+        "default" => return Ok(MatchingRules::default()),
+        "pikchr" => include_str!("presets/pikchr.yml"),
+        "graphviz" => include_str!("presets/graphviz.yml"),
+        "plantuml" => include_str!("presets/plantuml.yml"),
+        "mermaid" => include_str!("presets/mermaid.yml"),
+        "drawio" => include_str!("presets/drawio.yml"),
+        _ => bail!(ErrorKind::UnknownPreset(name.to_string())),
+    };
+    serde_yaml::from_str(yaml).map_err(|e| format!("invalid preset {}: {}", name, e).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn presets_are_valid() {
+        for name in PRESETS {
+            preset(name).unwrap().validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn extend_preset() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            matching:
+              extends: graphviz
+              key_attributes: [data-key]
+              rules:
+                - name: without_ids
+                  attr:
+                    exclude_attr: [id, class]
+                  include_text: true
+            "#,
+        )
+        .unwrap();
+        let graphviz = preset("graphviz").unwrap();
+        // The rule is replaced, everything else is kept
+        assert_eq!(config.matching.rules.len(), graphviz.rules.len());
+        let rule = config
+            .matching
+            .rules
+            .iter()
+            .find(|r| r.name == "without_ids")
+            .unwrap();
+        assert!(rule.attr.as_ref().unwrap().exclude_attr.contains("class"));
+        assert_eq!(config.matching.priorities, graphviz.priorities);
+        assert_eq!(config.matching.key_attributes, vec!["data-key".to_string()]);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_preset() {
+        assert!(serde_yaml::from_str::<Config>("matching: {extends: visio}").is_err());
+    }
+}
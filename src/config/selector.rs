@@ -0,0 +1,348 @@
+//! A subset of CSS selectors to choose the elements a matching rule applies to.
+//!
+//! Supported are type (`text`), universal (`*`), class (`.node`), id (`#a`),
+//! attribute presence (`[data-type]`) and attribute value (`[data-type=edge]`)
+//! selectors, the descendant (`g text`) and child (`g > text`) combinators
+//! and selector lists (`rect, circle`).
+
+use crate::errors::*;
+use crate::svg_data::Tag;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Selectors for a single element, like `g.node[data-type=edge]`.
+#[derive(Debug, Clone, Default)]
+struct Compound {
+    name: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, Option<String>)>,
+}
+
+impl Compound {
+    fn matches(&self, tag: &Tag) -> bool {
+        let attr = |name: &str| tag.args.get(name).map(|v| v.to_string());
+        if let Some(name) = &self.name {
+            if &tag.name != name {
+                return false;
+            }
+        }
+        if self.id.is_some() && attr("id") != self.id {
+            return false;
+        }
+        if !self.classes.is_empty() {
+            let classes = attr("class").unwrap_or_default();
+            let classes: Vec<&str> = classes.split_whitespace().collect();
+            if !self.classes.iter().all(|c| classes.contains(&c.as_str())) {
+                return false;
+            }
+        }
+        self.attributes
+            .iter()
+            .all(|(name, value)| match (attr(name), value) {
+                (Some(actual), Some(expected)) => &actual == expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// A selector without commas.
+#[derive(Debug, Clone)]
+struct Complex {
+    /// The rightmost compound, that has to match the element itself.
+    subject: Compound,
+    /// The other compounds from right to left, each with the combinator to its right.
+    ancestors: Vec<(Combinator, Compound)>,
+}
+
+impl Complex {
+    fn matches(&self, tag: &Tag, ancestors: &[&Tag]) -> bool {
+        self.subject.matches(tag) && Self::matches_ancestors(&self.ancestors, ancestors)
+    }
+
+    /// Checks the `compounds` against the `tags` (root first) above the last matched element.
+    fn matches_ancestors(compounds: &[(Combinator, Compound)], tags: &[&Tag]) -> bool {
+        let ((combinator, compound), rest) = match compounds.split_first() {
+            Some(first) => first,
+            None => return true,
+        };
+        match combinator {
+            Combinator::Child => match tags.split_last() {
+                Some((parent, above)) => {
+                    compound.matches(parent) && Self::matches_ancestors(rest, above)
+                }
+                None => false,
+            },
+            Combinator::Descendant => (0..tags.len())
+                .rev()
+                .any(|i| compound.matches(tags[i]) && Self::matches_ancestors(rest, &tags[..i])),
+        }
+    }
+}
+
+/// A parsed selector. It is (de)serialized as the selector string.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Selector {
+    source: String,
+    alternatives: Vec<Complex>,
+}
+
+impl Selector {
+    /// Whether the tag matches the selector.
+    /// `ancestors` are the ancestors of the tag, starting with the root.
+    pub fn matches(&self, tag: &Tag, ancestors: &[&Tag]) -> bool {
+        self.alternatives.iter().any(|c| c.matches(tag, ancestors))
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Selector> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        match parser.parse() {
+            Ok(alternatives) => Ok(Selector {
+                source: s.to_string(),
+                alternatives,
+            }),
+            Err(reason) => Err(ErrorKind::InvalidSelector(s.to_string(), reason).into()),
+        }
+    }
+}
+
+impl TryFrom<String> for Selector {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Selector> {
+        Selector::from_str(&s)
+    }
+}
+
+impl From<Selector> for String {
+    fn from(s: Selector) -> String {
+        s.source
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    /// Skips whitespace, returns true if there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn ident(&mut self, extra: &[char]) -> Option<String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '-' || c == '_' || extra.contains(&c) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if self.pos > start {
+            Some(self.chars[start..self.pos].iter().collect())
+        } else {
+            None
+        }
+    }
+
+    fn expect_ident(&mut self, extra: &[char]) -> std::result::Result<String, String> {
+        self.ident(extra).ok_or_else(|| match self.peek() {
+            Some(c) => format!("unexpected '{}' at {}", c, self.pos),
+            None => "unexpected end".to_string(),
+        })
+    }
+
+    fn expect(&mut self, expected: char) -> std::result::Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at {}", expected, self.pos))
+        }
+    }
+
+    fn parse(&mut self) -> std::result::Result<Vec<Complex>, String> {
+        let mut alternatives = vec![self.complex()?];
+        while self.peek().is_some() {
+            self.expect(',')?;
+            alternatives.push(self.complex()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn complex(&mut self) -> std::result::Result<Complex, String> {
+        self.skip_whitespace();
+        let mut compounds = vec![self.compound()?];
+        let mut combinators = Vec::new();
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            match self.peek() {
+                None | Some(',') => break,
+                Some('>') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    combinators.push(Combinator::Child);
+                }
+                Some(_) if had_whitespace => combinators.push(Combinator::Descendant),
+                Some(c) => return Err(format!("unexpected '{}' at {}", c, self.pos)),
+            }
+            compounds.push(self.compound()?);
+        }
+        let subject = compounds.pop().unwrap();
+        Ok(Complex {
+            subject,
+            ancestors: combinators
+                .into_iter()
+                .rev()
+                .zip(compounds.into_iter().rev())
+                .collect(),
+        })
+    }
+
+    fn compound(&mut self) -> std::result::Result<Compound, String> {
+        let start = self.pos;
+        let mut res = Compound::default();
+        if self.peek() == Some('*') {
+            self.pos += 1;
+        } else {
+            res.name = self.ident(&[]);
+        }
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.pos += 1;
+                    res.classes.push(self.expect_ident(&[])?);
+                }
+                Some('#') => {
+                    self.pos += 1;
+                    res.id = Some(self.expect_ident(&[])?);
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let name = self.expect_ident(&[':'])?;
+                    self.skip_whitespace();
+                    let value = if self.peek() == Some('=') {
+                        self.pos += 1;
+                        self.skip_whitespace();
+                        let value = self.value()?;
+                        self.skip_whitespace();
+                        Some(value)
+                    } else {
+                        None
+                    };
+                    self.expect(']')?;
+                    res.attributes.push((name, value));
+                }
+                _ => break,
+            }
+        }
+        if self.pos == start {
+            return Err(match self.peek() {
+                Some(c) => format!("unexpected '{}' at {}", c, self.pos),
+                None => "unexpected end".to_string(),
+            });
+        }
+        Ok(res)
+    }
+
+    /// An attribute value, either quoted or an identifier.
+    fn value(&mut self) -> std::result::Result<String, String> {
+        match self.peek() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().map(|c| c != quote).unwrap_or(false) {
+                    self.pos += 1;
+                }
+                let value = self.chars[start..self.pos].iter().collect();
+                self.expect(quote)?;
+                Ok(value)
+            }
+            _ => self.expect_ident(&[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use svg::node::Value;
+
+    fn tag(name: &str, args: &[(&str, &str)]) -> Tag {
+        Tag::new(
+            name.to_string(),
+            "".to_string(),
+            args.iter()
+                .map(|(k, v)| (k.to_string(), Value::from(v.to_string())))
+                .collect::<HashMap<String, Value>>(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn simple_selectors() {
+        let edge = tag("path", &[("class", "edge arrow"), ("data-type", "edge")]);
+        let matches = |s: &str| Selector::from_str(s).unwrap().matches(&edge, &[]);
+        assert!(matches("path"));
+        assert!(matches("*"));
+        assert!(matches(".arrow"));
+        assert!(matches("path.edge.arrow"));
+        assert!(matches("[data-type]"));
+        assert!(matches("[data-type=edge]"));
+        assert!(matches("[data-type = \"edge\"]"));
+        assert!(matches("rect, path"));
+        assert!(!matches("rect"));
+        assert!(!matches(".node"));
+        assert!(!matches("#edge"));
+        assert!(!matches("[data-type=node]"));
+    }
+
+    #[test]
+    fn combinators() {
+        let svg = tag("svg", &[]);
+        let node = tag("g", &[("class", "node")]);
+        let inner = tag("g", &[]);
+        let text = tag("text", &[]);
+        let child = Selector::from_str("g.node > text").unwrap();
+        let descendant = Selector::from_str("svg .node text").unwrap();
+        assert!(child.matches(&text, &[&svg, &node]));
+        assert!(!child.matches(&text, &[&svg, &node, &inner]));
+        assert!(descendant.matches(&text, &[&svg, &node, &inner]));
+        assert!(!descendant.matches(&text, &[&svg, &inner]));
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        assert!(Selector::from_str("").is_err());
+        assert!(Selector::from_str("g >").is_err());
+        assert!(Selector::from_str("[data-type").is_err());
+        assert!(Selector::from_str("a, ,b").is_err());
+    }
+}
@@ -1,27 +1,174 @@
 use flange_flat_tree::{Subtree, Tree};
 use std::cmp::Ordering::Equal;
 use std::cmp::{max_by, min_by};
+use std::collections::HashSet;
 use std::str::FromStr;
 
+use super::lifetime::{carry_forward, ids_of, ElementLifetime};
+use super::reorder;
+use super::report::MatchingReport;
 use super::step::DiffStep;
+use super::strategy::{strategy_anchors, MatchingStrategy};
 use crate::diff::hashmap_diff::HashMapDiff;
-use crate::diff::matching_ids::{get_matching_ids, MatchingIdGenerator};
+use crate::diff::matching_ids::{
+    get_matching_ids, get_matching_ids_by_edit_distance, get_matching_ids_by_gumtree,
+    resolve_collisions, Anchors, MatchingIdGenerator,
+};
 use crate::errors::*;
-use crate::svg_data::SVGWithIDs;
-use crate::{config, print_svg, SVG};
+use crate::svg_data::print_svg_with_id_attribute;
+use crate::svg_data::{Addressing, SVGWithIDs};
+use crate::{config, SVG};
 
 pub fn diff<'a>(
     origin: &'a SVG,
     target: &'a SVG,
     config: &'a config::Config,
-) -> (SVGWithIDs, SVGWithIDs, Vec<DiffStep>) {
+) -> Result<(SVGWithIDs<'a>, SVGWithIDs<'a>, Vec<DiffStep>)> {
+    let mut g = MatchingIdGenerator::new(&config.ids);
+    let (origin_with_ids, target_with_ids, diff, _) =
+        diff_impl(origin, target, config, None, &mut g, false)?;
+    Ok((origin_with_ids, target_with_ids, diff))
+}
+
+/// Like `diff`, but the matches of the `strategy` are used before
+/// the configured algorithm matches the remaining elements.
+pub fn diff_with_strategy<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    config: &'a config::Config,
+    strategy: &dyn MatchingStrategy,
+) -> Result<(SVGWithIDs<'a>, SVGWithIDs<'a>, Vec<DiffStep>)> {
+    let mut g = MatchingIdGenerator::new(&config.ids);
+    let (origin_with_ids, target_with_ids, diff, _) =
+        diff_impl(origin, target, config, Some(strategy), &mut g, false)?;
+    Ok((origin_with_ids, target_with_ids, diff))
+}
+
+/// Like `diff`, but also explains how the elements have been matched.
+pub fn diff_with_report<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    config: &'a config::Config,
+) -> Result<(
+    SVGWithIDs<'a>,
+    SVGWithIDs<'a>,
+    Vec<DiffStep>,
+    MatchingReport,
+)> {
+    let mut g = MatchingIdGenerator::new(&config.ids);
+    let (origin_with_ids, target_with_ids, diff, report) =
+        diff_impl(origin, target, config, None, &mut g, true)?;
+    Ok((
+        origin_with_ids,
+        target_with_ids,
+        diff,
+        report.unwrap_or_default(),
+    ))
+}
+
+type DiffResult<'a> = (
+    SVGWithIDs<'a>,
+    SVGWithIDs<'a>,
+    Vec<DiffStep>,
+    Option<MatchingReport>,
+);
+
+fn diff_impl<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    config: &'a config::Config,
+    strategy: Option<&dyn MatchingStrategy>,
+    g: &mut MatchingIdGenerator,
+    with_report: bool,
+) -> Result<DiffResult<'a>> {
+    config.validate()?;
+    if config.ignore.is_empty() {
+        return diff_svgs(origin, target, None, config, strategy, g, with_report);
+    }
+
+    // Match the SVGs without the ignored content (the strategy sees them like that too),
+    // then give the ids to the complete SVGs
+    let (origin_kept, origin_positions) = origin.without_ignored(&config.ignore);
+    let (target_kept, target_positions) = target.without_ignored(&config.ignore);
+    g.keep_carried(&origin_positions);
+    let (origin_with_ids, target_with_ids, diff, report) = diff_svgs(
+        &origin_kept,
+        &target_kept,
+        Some((target, &target_positions)),
+        config,
+        strategy,
+        g,
+        with_report,
+    )?;
+    let origin_ids = restore_ids(&origin_with_ids, &origin_positions, origin);
+    // The paths in the report refer to the complete SVGs
+    let restore_path = |copy: &SVG, positions: &[usize], svg: &SVG, path: &[usize]| {
+        svg.index_path(positions[copy.position_at_path(path).unwrap()])
+    };
+    let report = report.map(|r| {
+        r.map_paths(
+            &|path| restore_path(&origin_kept, &origin_positions, origin, path),
+            &|path| restore_path(&target_kept, &target_positions, target, path),
+        )
+    });
+    Ok((origin.with_ids(origin_ids), target_with_ids, diff, report))
+}
+
+/// The ids of the elements of `svg`, given the ids of a copy with some
+/// elements left out (see `SVG::without_ignored`).
+fn restore_ids(copy: &SVGWithIDs, positions: &[usize], svg: &SVG) -> Vec<Option<String>> {
+    let mut res = vec![None; svg.tags.node_count()];
+    for (copy_pos, pos) in positions.iter().enumerate() {
+        res[*pos] = copy.at_pos(copy_pos).value().1.clone();
+    }
+    res
+}
+
+/// Diffs the SVGs. If the SVGs are copies without the ignored content (see `Ignore`),
+/// `complete_target` is the complete target and the position in it of every element
+/// of the copy. The added elements are printed from it, and the target with ids is returned for it.
+fn diff_svgs<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    complete_target: Option<(&'a SVG, &[usize])>,
+    config: &config::Config,
+    strategy: Option<&dyn MatchingStrategy>,
+    g: &mut MatchingIdGenerator,
+    with_report: bool,
+) -> Result<DiffResult<'a>> {
+    config.matching.validate_keys(origin)?;
+    config.matching.validate_keys(target)?;
+    let seed = match strategy {
+        Some(strategy) => strategy_anchors(strategy, origin, target)?,
+        None => Anchors::default(),
+    };
+
     // Track the result
     let mut diff = Vec::new();
 
     // Match using tagging ids
-    let mut g = MatchingIdGenerator::new();
-    let (origin_with_states, target_with_states) =
-        get_matching_ids(origin, target, &config.matching, &mut g);
+    if config.ids.content_derived {
+        g.derive_from(origin, target);
+    }
+    let (origin_with_states, target_with_states) = match &config.algorithm {
+        config::Algorithm::HashPriority => {
+            get_matching_ids(origin, target, &config.matching, &seed, g)?
+        }
+        config::Algorithm::TreeEditDistance(costs) => {
+            get_matching_ids_by_edit_distance(origin, target, costs, &seed, g)
+        }
+        config::Algorithm::GumTree(options) => {
+            get_matching_ids_by_gumtree(origin, target, options, &seed, g)
+        }
+    };
+    let (origin_with_states, target_with_states) = resolve_collisions(
+        origin,
+        target,
+        origin_with_states,
+        target_with_states,
+        &config.ids.attribute,
+        g,
+    );
 
     // Build the svg with ids
     // let origin_with_ids = origin.with_ids(&origin_ids);
@@ -29,6 +176,30 @@ pub fn diff<'a>(
         target_with_states.replace_map_flange(|s| s.1.as_ref().map(|s| s.get_id()));
     let origin_with_ids =
         origin_with_states.replace_map_flange(|s| s.1.as_ref().map(|s| s.get_id()));
+    let origin_addressing = Addressing::new(&origin_with_ids, &config.ids.attribute);
+    let target_addressing = Addressing::new(&target_with_ids, &config.ids.attribute);
+    // The elements as they are printed, with the ignored content
+    let (printed_target, printed_positions) = match complete_target {
+        Some((complete, positions)) => (
+            complete.with_ids(restore_ids(&target_with_ids, positions, complete)),
+            positions.to_vec(),
+        ),
+        None => (
+            target.with_ids(ids_of(&target_with_ids)),
+            (0..target.tags.node_count()).collect(),
+        ),
+    };
+    let report = if with_report {
+        Some(MatchingReport::build(
+            origin,
+            target,
+            &origin_with_states,
+            &target_with_states,
+            &config.matching.scoring.clone().unwrap_or_default(),
+        ))
+    } else {
+        None
+    };
 
     // 1. Add unmatched tags in the target
     target_with_states.for_each(|s| {
@@ -38,83 +209,65 @@ pub fn diff<'a>(
             .map(|s| s.is_unmatched())
             .unwrap_or(false)
         {
-            diff.push(DiffStep::add(&target_with_ids.at_pos(s.get_pos())))
+            diff.push(DiffStep::add(
+                &target_with_ids.at_pos(s.get_pos()),
+                &printed_target.at_pos(printed_positions[s.get_pos()]),
+                &target_addressing,
+            ))
         };
     });
 
-    // 2. remove unmatched tags
-    origin_with_states.for_each(|s| {
-        if s.value()
-            .1
-            .as_ref()
-            .map(|s| s.is_unmatched())
-            .unwrap_or(false)
-        {
-            diff.push(DiffStep::remove(&origin_with_ids.at_pos(s.get_pos())))
-        }
-    });
-
-    // 3. reorder items
+    // 2. reorder items
+    // (the origin positions of the elements moved to another parent are kept for the removes)
+    let mut moved_away = HashSet::new();
     target_with_states.for_each(|s| {
         if let Some(target_state) = s.value().1 {
-            if target_state.changes_in_subtree() {
-                if let Some(origin_index) = target_state.get_origin_index() {
-                    // Get the ids of both origin and target, that have not been removed or added
-                    let mut current_childs = Vec::new();
-                    for child in origin_with_states.at_pos(origin_index).children() {
-                        if !child.value().1.as_ref().unwrap().is_unmatched() {
-                            current_childs.push(child);
+            if target_state.is_unmatched() {
+                // Matched elements are moved into the added element
+                for child in s.children() {
+                    if let Some(child_state) = child.value().1.as_ref() {
+                        if !child_state.is_unmatched() {
+                            moved_away.extend(child_state.get_origin_index());
+                            diff.push(DiffStep::move_element(
+                                &target_with_ids.at_pos(child.get_pos()),
+                            ));
                         }
                     }
+                }
+            } else if target_state.changes_in_subtree() {
+                if let Some(origin_index) = target_state.get_origin_index() {
+                    // Get the target children, that have not been added
+                    // and that have not been moved from another parent.
                     let mut target_childs = Vec::new();
                     for child in s.children() {
-                        if !child.value().1.as_ref().unwrap().is_unmatched() {
-                            target_childs.push(child);
+                        let child_state = child.value().1.as_ref().unwrap();
+                        if child_state.is_unmatched() {
+                            continue;
                         }
-                    }
-                    assert!(current_childs.len() == target_childs.len());
-                    // Find those, that don't match (that means must be reordered)!
-                    let mut unmatched_indices = Vec::new();
-                    for (index, target_child) in target_childs.into_iter().enumerate() {
-                        if current_childs[index]
-                            .value()
-                            .1
-                            .as_ref()
-                            .unwrap()
-                            .get_target_index()
-                            .unwrap()
-                            != target_child.get_pos()
+                        if origin_with_states
+                            .at_pos(child_state.get_origin_index().unwrap())
+                            .parent()
+                            .map(|p| p.get_pos())
+                            == Some(origin_index)
                         {
-                            {
-                                // modified the origin child ids to match
-                                let swap_index = {
-                                    current_childs
-                                        .iter()
-                                        .enumerate()
-                                        .find(|(_index, child)| {
-                                            child
-                                                .value()
-                                                .1
-                                                .as_ref()
-                                                .unwrap()
-                                                .get_target_index()
-                                                .unwrap()
-                                                == target_child.get_pos()
-                                        })
-                                        .unwrap()
-                                        .0
-                                };
-                                // Swap the indices
-                                current_childs.swap(swap_index, index);
-                                // Remember the target child
-                                unmatched_indices.push(target_child);
-                            }
+                            target_childs.push(child);
+                        } else {
+                            // Moved here from another parent
+                            moved_away.extend(child_state.get_origin_index());
+                            diff.push(DiffStep::move_element(
+                                &target_with_ids.at_pos(child.get_pos()),
+                            ));
                         }
                     }
-                    // Push those unmatched indices
-                    for target in unmatched_indices {
+                    // Only the children that changed their relative order are moved
+                    // (the children are indexed in document order, so we can use their index).
+                    let origin_positions: Vec<usize> = target_childs
+                        .iter()
+                        .map(|c| c.value().1.as_ref().unwrap().get_origin_index().unwrap())
+                        .collect();
+                    for index in reorder::elements_to_move(&origin_positions) {
                         diff.push(DiffStep::move_element(
-                            &target_with_ids.at_pos(target.get_pos()),
+                            &target_with_ids.at_pos(target_childs[index].get_pos()),
                         ));
                     }
                 }
@@ -122,6 +275,25 @@ pub fn diff<'a>(
         }
     });
 
+    // 3. remove unmatched tags
+    // (after the matched elements in them have been moved out)
+    let is_unmatched = |pos: usize| {
+        origin_with_states
+            .get_flange(pos)
+            .as_ref()
+            .map(|s| s.is_unmatched())
+            .unwrap_or(false)
+    };
+    origin_with_states.for_each(|s| {
+        if is_unmatched(s.get_pos()) {
+            // The siblings before have already been removed if they are unmatched
+            diff.push(DiffStep::remove(
+                &origin_with_ids.at_pos(s.get_pos()),
+                |pos, before| !moved_away.contains(&pos) && !(before && is_unmatched(pos)),
+            ))
+        }
+    });
+
     // 4. finally change items
     target_with_states.for_each(|s| {
         if let Some(target_state) = s.value().1 {
@@ -129,15 +301,31 @@ pub fn diff<'a>(
                 if let Some(origin_index) = target_state.get_origin_index() {
                     let origin_tag = origin.tags.at_pos(origin_index).value();
                     let target_tag = s.value().0;
-                    if origin_tag.text != target_tag.text {
-                        diff.push(DiffStep::text_change(
-                            target_state.get_id(),
-                            target_tag.text.clone(),
-                        ))
-                    }
-                    let hash_diff = HashMapDiff::create(&origin_tag.args, &target_tag.args);
-                    if !hash_diff.is_empty() {
-                        diff.push(DiffStep::change(target_state.get_id(), hash_diff))
+                    if origin_tag.name != target_tag.name {
+                        // Basic shapes matched with another type of shape
+                        // (see `MatchingRule::canonical_shapes`)
+                        diff.push(DiffStep::change_shape(
+                            &target_with_ids.at_pos(s.get_pos()),
+                            &printed_target.at_pos(printed_positions[s.get_pos()]),
+                            origin_tag,
+                            &target_addressing,
+                        ));
+                    } else {
+                        if origin_tag.text != target_tag.text {
+                            diff.push(DiffStep::text_change(
+                                target_state.get_id(),
+                                target_tag.text.clone(),
+                            ))
+                        }
+                        let hash_diff = HashMapDiff::create(&origin_tag.args, &target_tag.args);
+                        if !hash_diff.is_empty() {
+                            diff.extend(DiffStep::change(
+                                target_state.get_id(),
+                                hash_diff,
+                                &origin_addressing,
+                                &target_addressing,
+                            ))
+                        }
                     }
                 }
             }
@@ -145,16 +333,50 @@ pub fn diff<'a>(
     });
 
     // Return the result
-    (origin_with_ids, target_with_ids, diff)
+    Ok((origin_with_ids, printed_target, diff, report))
 }
 
 pub fn diffs<'a>(
     tags: &'a Vec<SVG>,
     min_view_box: Option<svgtypes::ViewBox>,
     config: &'a config::Config,
-) -> (Vec<SVGWithIDs<'a>>, Vec<Vec<DiffStep>>, svgtypes::ViewBox) {
+) -> Result<(Vec<SVGWithIDs<'a>>, Vec<Vec<DiffStep>>, svgtypes::ViewBox)> {
+    let (svgs, diffs, view_box, _, _) = diffs_impl(tags, min_view_box, config, false)?;
+    Ok((svgs, diffs, view_box))
+}
+
+/// Like `diffs`, but also returns the lifetime of every element (the frames it is in).
+pub fn diffs_with_lifetimes<'a>(
+    tags: &'a Vec<SVG>,
+    min_view_box: Option<svgtypes::ViewBox>,
+    config: &'a config::Config,
+) -> Result<(
+    Vec<SVGWithIDs<'a>>,
+    Vec<Vec<DiffStep>>,
+    svgtypes::ViewBox,
+    Vec<ElementLifetime>,
+)> {
+    let (svgs, diffs, view_box, _, lifetimes) = diffs_impl(tags, min_view_box, config, false)?;
+    Ok((svgs, diffs, view_box, lifetimes))
+}
+
+type DiffsResult<'a> = (
+    Vec<SVGWithIDs<'a>>,
+    Vec<Vec<DiffStep>>,
+    svgtypes::ViewBox,
+    Vec<MatchingReport>,
+    Vec<ElementLifetime>,
+);
+
+fn diffs_impl<'a>(
+    tags: &'a Vec<SVG>,
+    min_view_box: Option<svgtypes::ViewBox>,
+    config: &'a config::Config,
+    with_reports: bool,
+) -> Result<DiffsResult<'a>> {
     let mut svgs = Vec::new();
     let mut diffs = Vec::new();
+    let mut reports = Vec::new();
 
     // Find the biggest all containing viewbox
     let mut all_viewbox =
@@ -186,21 +408,86 @@ pub fn diffs<'a>(
         }
     }
 
-    for index in 0..tags.len() - 1 {
+    // One generator for all diffs, so the ids are unique in all of them.
+    // The ids of the target of a diff are carried forward to the next one,
+    // so the elements keep their ids in the whole sequence.
+    let mut g = MatchingIdGenerator::new(&config.ids);
+    let mut carried = Vec::new();
+    let mut frames = Vec::new();
+    for index in 0..tags.len().saturating_sub(1) {
+        g.carry(carried.clone());
         // We cannot borrow mutable twice, so we do a trick
-        let d: (SVGWithIDs, SVGWithIDs, Vec<DiffStep>) =
-            diff(&tags[index], &tags[index + 1], config);
-        svgs.push(d.0);
+        let d: DiffResult = diff_impl(
+            &tags[index],
+            &tags[index + 1],
+            config,
+            None,
+            &mut g,
+            with_reports,
+        )?;
+        let frame: Vec<Option<String>> = ids_of(&d.0)
+            .into_iter()
+            .enumerate()
+            .map(|(pos, id)| id.or_else(|| carried.get(pos).cloned().flatten()))
+            .collect();
+        carried = carry_forward(&d.0, &d.1, &frame);
+        // The carried ids are printed too, so every id of the lifetimes is in the frames
+        svgs.push(tags[index].with_ids(frame.clone()));
+        frames.push(frame);
         diffs.push(d.2);
+        reports.extend(d.3);
+    }
+    if !tags.is_empty() {
+        frames.push(carried);
     }
 
-    (svgs, diffs, all_viewbox)
+    Ok((
+        svgs,
+        diffs,
+        all_viewbox,
+        reports,
+        ElementLifetime::from_frames(&frames),
+    ))
 }
 
 pub fn diff_from_strings(
     svg_strings: &[String],
     config: &config::Config,
 ) -> Result<(Vec<String>, Vec<Vec<DiffStep>>)> {
+    let (svgs, diffs, _, _) = diff_from_strings_impl(svg_strings, config, false)?;
+    Ok((svgs, diffs))
+}
+
+/// Like `diff_from_strings`, but also returns a `MatchingReport` for every diff.
+pub fn diff_from_strings_with_reports(
+    svg_strings: &[String],
+    config: &config::Config,
+) -> Result<(Vec<String>, Vec<Vec<DiffStep>>, Vec<MatchingReport>)> {
+    let (svgs, diffs, reports, _) = diff_from_strings_impl(svg_strings, config, true)?;
+    Ok((svgs, diffs, reports))
+}
+
+/// Like `diff_from_strings`, but also returns the lifetime of every element.
+pub fn diff_from_strings_with_lifetimes(
+    svg_strings: &[String],
+    config: &config::Config,
+) -> Result<(Vec<String>, Vec<Vec<DiffStep>>, Vec<ElementLifetime>)> {
+    let (svgs, diffs, _, lifetimes) = diff_from_strings_impl(svg_strings, config, false)?;
+    Ok((svgs, diffs, lifetimes))
+}
+
+type DiffFromStringsResult = (
+    Vec<String>,
+    Vec<Vec<DiffStep>>,
+    Vec<MatchingReport>,
+    Vec<ElementLifetime>,
+);
+
+fn diff_from_strings_impl(
+    svg_strings: &[String],
+    config: &config::Config,
+    with_reports: bool,
+) -> Result<DiffFromStringsResult> {
     // Convert the input
     let svgs: Result<Vec<SVG>> = svg_strings
         .iter()
@@ -212,21 +499,29 @@ pub fn diff_from_strings(
     let svgs = svgs?;
 
     // Create the diffs!
-    let (svg_with_ids, diff, view_box) = diffs(&svgs, None, config);
+    let (svg_with_ids, diff, view_box, reports, lifetimes) =
+        diffs_impl(&svgs, None, config, with_reports)?;
 
     // Create result svgs
     let mut res_svgs = Vec::new();
     for svg in svg_with_ids.into_iter() {
-        res_svgs.push(print_svg(&svg, Some(&view_box)));
+        res_svgs.push(print_svg_with_id_attribute(
+            &svg,
+            Some(&view_box),
+            &config.ids.attribute,
+        ));
     }
 
-    Ok((res_svgs, diff))
+    Ok((res_svgs, diff, reports, lifetimes))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{
+        Algorithm, Config, EditCosts, FuzzyText, GumTreeOptions, IdOptions, Ignore, MatchingRule,
+        ProximityRule, Selector, SiblingRule, SimilarityScoring,
+    };
 
     #[test]
     fn test_remove() {
@@ -374,4 +669,1176 @@ mod test {
         assert_eq!(diffs[0].len(), 1);
         assert!(diffs[0][0].is_move());
     }
+
+    #[test]
+    fn move_to_front() {
+        // setup
+        let origin = r###"
+        <svg>
+          <circle r="1"/>
+          <circle r="2"/>
+          <circle r="3"/>
+          <circle r="4"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <circle r="4"/>
+          <circle r="1"/>
+          <circle r="2"/>
+          <circle r="3"/>
+        </svg>
+        "###
+        .to_string();
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &Config::default()).unwrap();
+
+        // Test, only the moved circle is moved, the others keep their order
+        assert_eq!(diffs[0].len(), 1);
+        assert!(diffs[0][0].is_move());
+    }
+
+    #[test]
+    fn scored_matching() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect x="0" width="10" height="10"/>
+          <rect x="100" width="10" height="10"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <rect x="102" width="10" height="10"/>
+          <rect x="1" width="10" height="10"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.scoring = Some(SimilarityScoring::default());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the boxes are not swapped, so they have to be reordered
+        assert_eq!(diffs[0].len(), 3);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_move()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_change()).count(), 2);
+    }
+
+    #[test]
+    fn scored_matching_min_score() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect x="0" width="10" height="10" fill="red"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <rect x="500" width="30" height="10" fill="blue"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.scoring = Some(SimilarityScoring::default());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin.clone(), target.clone()], &config).unwrap();
+
+        // Test, the rects are too different to be matched
+        assert_eq!(diffs[0].len(), 2);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_remove()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_add()).count(), 1);
+
+        // Act, without a minimum score
+        config.matching.scoring.as_mut().unwrap().min_score = 0.0;
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the rect is changed
+        assert_eq!(diffs[0].len(), 1);
+        assert!(diffs[0][0].is_change());
+    }
+
+    #[test]
+    fn proximity_matching() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect x="0" width="10" height="10"/>
+          <rect x="100" width="10" height="10"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <rect x="102" width="10" height="10" fill="red"/>
+          <rect x="1" width="10" height="10" fill="red"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        let mut nearby = MatchingRule::new_all_rule();
+        nearby.name = "nearby".to_string();
+        nearby.attr = None;
+        nearby.sort_children = true;
+        nearby.proximity = Some(ProximityRule {
+            max_distance: Some(10.0),
+            min_overlap: None,
+        });
+        config.matching.rules.push(nearby);
+        config.matching.priorities.insert(0, "nearby".to_string());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the rects are matched with the close ones, not in document order
+        assert_eq!(diffs[0].len(), 3);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_move()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_change()).count(), 2);
+    }
+
+    fn fuzzy_label_rule() -> MatchingRule {
+        let mut label = MatchingRule::new_all_rule();
+        label.name = "label".to_string();
+        label.attr = None;
+        label.fuzzy_text = Some(FuzzyText {
+            min_similarity: 0.8,
+            metric: Default::default(),
+        });
+        label
+    }
+
+    #[test]
+    fn fuzzy_text() {
+        // setup
+        let origin = r###"
+        <svg>
+          <text>UserService</text>
+          <text>Database</text>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <text>Data base</text>
+          <text>UserServices</text>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.rules.push(fuzzy_label_rule());
+        config.matching.priorities.insert(0, "label".to_string());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the texts are matched with the similar ones
+        assert_eq!(diffs[0].len(), 3);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_move()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_text_change()).count(), 2);
+    }
+
+    #[test]
+    fn fuzzy_text_of_sibling() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect width="10"/>
+          <text>UserService</text>
+          <rect width="20"/>
+          <text>Database</text>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <rect width="21"/>
+          <text>Database</text>
+          <rect width="11"/>
+          <text>UserServices</text>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        let mut next_is_label = MatchingRule::new_all_without_subtrees_rule();
+        next_is_label.name = "next_is_label".to_string();
+        next_is_label.attr = None;
+        next_is_label.next_sibling_rule = Some("label".to_string());
+        config.matching.rules.push(fuzzy_label_rule());
+        config.matching.rules.push(next_is_label);
+        config.matching.priorities.insert(0, "label".to_string());
+        config
+            .matching
+            .priorities
+            .insert(1, "next_is_label".to_string());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the rects are matched by the text next to them
+        assert!(!diffs[0].iter().any(|d| d.is_add() || d.is_remove()));
+        assert_eq!(diffs[0].iter().filter(|d| d.is_change()).count(), 2);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_text_change()).count(), 1);
+    }
+
+    #[test]
+    fn move_to_other_parent() {
+        // setup
+        let origin = r###"
+        <svg>
+          <g id="a"><circle r="5"/></g>
+          <g id="b"><rect width="5"/></g>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <g id="a"></g>
+          <g id="b"><rect width="5"/><circle r="5"/></g>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        let mut node_rule = MatchingRule::new_all_without_subtrees_rule();
+        node_rule.name = "node_only".to_string();
+        config.matching.rules.push(node_rule);
+        config.matching.priorities.push("node_only".to_string());
+        config.matching.cross_parent_priorities = vec!["all".to_string()];
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test
+        assert_eq!(diffs[0].len(), 1);
+        assert!(diffs[0][0].is_move());
+        assert_eq!(
+            serde_json::to_value(&diffs[0][0]).unwrap()["new_parent_id"],
+            "b"
+        );
+    }
+
+    #[test]
+    fn remove_next_to_moved_element() {
+        // setup
+        let origin = r###"
+        <svg>
+          <g id="a"><line x2="9"/><circle r="5"/><ellipse id="e" rx="3"/></g>
+          <g id="b"><rect width="5"/></g>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <g id="a"><ellipse id="e" rx="3"/></g>
+          <g id="b"><rect width="5"/><circle r="5"/></g>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        let mut node_rule = MatchingRule::new_all_without_subtrees_rule();
+        node_rule.name = "node_only".to_string();
+        config.matching.rules.push(node_rule);
+        config.matching.priorities.push("node_only".to_string());
+        config.matching.cross_parent_priorities = vec!["all".to_string()];
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the circle is moved away before the line is removed
+        assert_eq!(diffs[0].len(), 2);
+        assert!(diffs[0][0].is_move());
+        assert!(diffs[0][1].is_remove());
+        let remove = serde_json::to_value(&diffs[0][1]).unwrap();
+        assert_eq!(remove["parent_id"], "a");
+        assert_eq!(remove["prev_child_id"], serde_json::Value::Null);
+        assert_eq!(remove["next_child_id"], "e");
+    }
+
+    #[test]
+    fn key_attributes() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect data-key="a" x="0"/>
+          <rect data-key="b" x="50"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <rect data-key="b" x="0"/>
+          <rect data-key="a" x="50"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.key_attributes = vec!["data-key".to_string()];
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the keyed elements have been matched and swapped
+        assert_eq!(diffs[0].iter().filter(|d| d.is_move()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_change()).count(), 2);
+    }
+
+    #[test]
+    fn keyed_element_in_removed_group() {
+        // setup
+        let origin = r###"
+        <svg>
+          <g class="old"><g><rect data-key="a" width="10"/></g></g>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <circle r="5"/>
+          <rect data-key="a" width="10"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.key_attributes = vec!["data-key".to_string()];
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin.clone(), target.clone()], &config).unwrap();
+
+        // Test, the rect is moved out of the group before it is removed
+        let steps = |action: &str| {
+            diffs[0]
+                .iter()
+                .map(|d| serde_json::to_value(d).unwrap())
+                .filter(|d| d["action"] == action)
+                .collect::<Vec<serde_json::Value>>()
+        };
+        assert_eq!(steps("move").len(), 1);
+        assert_eq!(steps("remove").len(), 1);
+        let added = steps("add");
+        assert_eq!(added.len(), 1);
+        assert!(added[0]["svg"].as_str().unwrap().starts_with("<circle"));
+
+        // Act, the other way round
+        let (_svgs, diffs) = diff_from_strings(&[target, origin], &config).unwrap();
+
+        // Test, the groups are added and the rect is moved into them
+        let steps = |action: &str| {
+            diffs[0]
+                .iter()
+                .map(|d| serde_json::to_value(d).unwrap())
+                .filter(|d| d["action"] == action)
+                .collect::<Vec<serde_json::Value>>()
+        };
+        assert_eq!(steps("move").len(), 1);
+        assert_eq!(steps("remove").len(), 1);
+        let added = steps("add");
+        assert_eq!(added.len(), 2);
+        assert!(added
+            .iter()
+            .all(|a| !a["svg"].as_str().unwrap().contains("rect")));
+        assert_eq!(steps("move")[0]["new_parent_id"], added[1]["id"]);
+    }
+
+    #[test]
+    fn change_shape() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect x="0" y="0" width="10" height="10" fill="red"/>
+          <circle cx="50" cy="5" r="5"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <path d="M 0 0 L 10 0 L 10 10 L 0 10 Z" fill="blue"/>
+          <circle cx="50" cy="5" r="5"/>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        let mut outline = MatchingRule::new_all_without_subtrees_rule();
+        outline.name = "outline".to_string();
+        outline.canonical_shapes = true;
+        outline.attr.as_mut().unwrap().included_attr = Some(["d".to_string()].into());
+        config.matching.rules.push(outline);
+        config.matching.priorities.insert(0, "outline".to_string());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the rect is turned into the path
+        assert_eq!(diffs[0].len(), 1);
+        assert!(diffs[0][0].is_shape_change());
+        let step = serde_json::to_value(&diffs[0][0]).unwrap();
+        assert_eq!(step["action"], "change_shape");
+        assert!(step["start_path"]
+            .as_str()
+            .unwrap()
+            .starts_with("M 0 0 C 3.3"));
+        assert_eq!(step["start_path"], step["end_path"]);
+        assert!(step["svg"].as_str().unwrap().starts_with("<path"));
+    }
+
+    #[test]
+    fn change_shape_paths() {
+        // Any basic shape matches any other
+        let mut config = Config::default();
+        let mut any_shape = MatchingRule::new_all_without_subtrees_rule();
+        any_shape.name = "any_shape".to_string();
+        any_shape.canonical_shapes = true;
+        any_shape.attr.as_mut().unwrap().included_attr = Some(Default::default());
+        config.matching.rules.push(any_shape);
+        config
+            .matching
+            .priorities
+            .insert(0, "any_shape".to_string());
+        let paths = |origin: &str, target: &str| {
+            let svg = |shape: &str| format!("<svg>{}</svg>", shape);
+            let (_svgs, diffs) = diff_from_strings(&[svg(origin), svg(target)], &config).unwrap();
+            assert_eq!(diffs[0].len(), 1);
+            let step = serde_json::to_value(&diffs[0][0]).unwrap();
+            let path = |name: &str| step[name].as_str().unwrap().to_string();
+            (path("start_path"), path("end_path"))
+        };
+        let commands =
+            |path: &str| -> String { path.chars().filter(|c| c.is_alphabetic()).collect() };
+
+        // Lines and relative commands are turned into absolute curves
+        let (start, end) = paths(
+            r#"<circle cx="5" cy="5" r="5"/>"#,
+            r#"<path d="m 0 0 h 10 v 10 h -10 z"/>"#,
+        );
+        assert_eq!(commands(&start), "MCCCCZ");
+        assert_eq!(commands(&end), "MCCCCZ");
+        assert!(start.starts_with("M 10 5 C "));
+        assert!(end.starts_with("M 0 0 C "));
+
+        // The triangle is split to have as many curves as the rect
+        let (start, end) = paths(
+            r#"<rect width="10" height="10"/>"#,
+            r#"<path d="m 0 0 l 10 0 l -5 10 z"/>"#,
+        );
+        assert_eq!(commands(&start), "MCCCCZ");
+        assert_eq!(commands(&end), "MCCCCZ");
+        assert!(end.ends_with(", 0 0 Z"));
+    }
+
+    #[test]
+    fn tree_edit_distance() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect x="0" width="10"/>
+          <circle r="5"/>
+          <text>a</text>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <circle r="5"/>
+          <path d="M 0 0 L 1 1"/>
+          <text>b</text>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config {
+            algorithm: Algorithm::TreeEditDistance(EditCosts::default()),
+            ..Default::default()
+        };
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin.clone(), target.clone()], &config).unwrap();
+
+        // Test, the rect is removed and the path is added
+        assert_eq!(diffs[0].iter().filter(|d| d.is_remove()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_add()).count(), 1);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_text_change()).count(), 1);
+        assert_eq!(diffs[0].len(), 3);
+
+        // Act, with expensive adds and removes
+        config.algorithm = Algorithm::TreeEditDistance(EditCosts {
+            insert: 5.0,
+            delete: 5.0,
+            ..Default::default()
+        });
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the shapes are changed into each other instead
+        assert!(!diffs[0].iter().any(|d| d.is_add() || d.is_remove()));
+        assert_eq!(diffs[0].iter().filter(|d| d.is_shape_change()).count(), 2);
+        assert_eq!(diffs[0].iter().filter(|d| d.is_text_change()).count(), 1);
+    }
+
+    #[test]
+    fn gumtree_regrouped() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect width="10"/>
+          <text>a</text>
+          <circle r="5"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <g class="group">
+            <rect width="10"/>
+            <text>a</text>
+          </g>
+          <circle r="5"/>
+        </svg>
+        "###
+        .to_string();
+        let config = Config {
+            algorithm: Algorithm::GumTree(GumTreeOptions::default()),
+            ..Default::default()
+        };
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the group is added and the existing elements are moved into it
+        assert_eq!(diffs[0].len(), 3);
+        assert!(diffs[0][0].is_add());
+        let added = serde_json::to_value(&diffs[0][0]).unwrap();
+        assert!(!added["svg"].as_str().unwrap().contains("rect"));
+        assert!(diffs[0][1..].iter().all(|d| d.is_move()));
+        let moved = serde_json::to_value(&diffs[0][1]).unwrap();
+        assert_eq!(moved["new_parent_id"], added["id"]);
+    }
+
+    #[test]
+    fn ignored_content() {
+        // setup
+        let origin = r###"
+        <svg data-build="1">
+          <metadata>random 1</metadata>
+          <text class="timestamp">10:00</text>
+          <rect data-build="1" width="10"/>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg data-build="2">
+          <metadata>random 2</metadata>
+          <text class="timestamp">10:05</text>
+          <rect data-build="2" width="20"/>
+        </svg>
+        "###
+        .to_string();
+        let config = Config {
+            ignore: Ignore {
+                elements: vec![Selector::from_str("metadata, text.timestamp").unwrap()],
+                attributes: vec!["data-build".to_string()],
+            },
+            ..Default::default()
+        };
+
+        // Act
+        let (svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, only the width of the rect changes
+        assert_eq!(diffs[0].len(), 1);
+        let change = serde_json::to_value(&diffs[0][0]).unwrap();
+        assert_eq!(change["changes"].as_array().unwrap().len(), 1);
+        assert_eq!(change["changes"][0]["prop"], "width");
+        // The ignored content is still printed
+        assert!(svgs[0].contains("random 1"));
+        assert!(svgs[0].contains("10:00"));
+        assert!(svgs[0].contains("data-build"));
+    }
+
+    #[test]
+    fn added_element_with_ignored_content() {
+        // setup
+        let origin = r#"<svg><rect width="1"/></svg>"#.to_string();
+        let target =
+            r#"<svg><rect width="1"/><g class="node"><desc>a node</desc><circle r="1"/></g></svg>"#
+                .to_string();
+        let config = Config {
+            ignore: Ignore {
+                elements: vec![Selector::from_str("desc").unwrap()],
+                attributes: vec!["class".to_string()],
+            },
+            ..Default::default()
+        };
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the group is added with its ignored attribute and child
+        assert_eq!(diffs[0].len(), 1);
+        assert!(diffs[0][0].is_add());
+        let add = serde_json::to_value(&diffs[0][0]).unwrap();
+        let svg = add["svg"].as_str().unwrap();
+        assert!(svg.contains(r#"class="node""#));
+        assert!(svg.contains("<desc"));
+        assert!(svg.contains("a node"));
+        assert!(svg.contains("<circle"));
+    }
+
+    #[test]
+    fn deterministic_ids() {
+        // setup
+        let svgs = [
+            r#"<svg><rect width="1" fill="red" x="0"/><circle r="1"/></svg>"#.to_string(),
+            r#"<svg><circle r="2"/><rect width="2" fill="blue" x="1"/></svg>"#.to_string(),
+            r#"<svg><rect width="2" fill="blue" x="1"/><line x2="1"/></svg>"#.to_string(),
+        ];
+        for ids in [
+            IdOptions {
+                seed: Some(7),
+                ..Default::default()
+            },
+            IdOptions {
+                content_derived: true,
+                ..Default::default()
+            },
+        ] {
+            let config = Config {
+                ids,
+                ..Default::default()
+            };
+
+            // Act
+            let first = diff_from_strings(&svgs, &config).unwrap();
+            let second = diff_from_strings(&svgs, &config).unwrap();
+
+            // Test, the output is the same on every run
+            assert_eq!(first.0, second.0);
+            assert_eq!(
+                serde_json::to_string(&first.1).unwrap(),
+                serde_json::to_string(&second.1).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn ids_across_frames() {
+        // setup
+        let frames = vec![
+            SVG::parse_svg_string(r#"<svg><circle r="1"/><text>a</text></svg>"#).unwrap(),
+            SVG::parse_svg_string(r#"<svg><rect width="1"/><circle r="2"/><text>a</text></svg>"#)
+                .unwrap(),
+            SVG::parse_svg_string(r#"<svg><circle r="3"/><text>a</text></svg>"#).unwrap(),
+        ];
+        let config = Config::default();
+
+        // Act
+        let (svgs, _diffs, _view_box, lifetimes) =
+            diffs_with_lifetimes(&frames, None, &config).unwrap();
+
+        // Test, the circle keeps its id in both diffs
+        let id_at = |frame: usize, child: usize| {
+            let pos = frames[frame].child_positions(frames[frame].root_position())[child];
+            svgs[frame].at_pos(pos).value().1.clone().unwrap()
+        };
+        let circle = id_at(0, 0);
+        assert_eq!(id_at(1, 1), circle);
+        let lifetime = |id: &str| lifetimes.iter().find(|l| l.id == id).unwrap();
+        assert_eq!(lifetime(&circle).first_frame, 0);
+        assert_eq!(lifetime(&circle).last_frame, 2);
+        // The rect is only in the second frame
+        let rect = id_at(1, 0);
+        assert_eq!(lifetime(&rect).first_frame, 1);
+        assert_eq!(lifetime(&rect).last_frame, 1);
+    }
+
+    #[test]
+    fn no_frames() {
+        let (svgs, diffs, _view_box, lifetimes) =
+            diffs_with_lifetimes(&vec![], None, &Config::default()).unwrap();
+        assert!(svgs.is_empty());
+        assert!(diffs.is_empty());
+        assert!(lifetimes.is_empty());
+    }
+
+    #[test]
+    fn carried_ids_are_printed() {
+        // setup
+        let frames = vec![
+            SVG::parse_svg_string(r#"<svg><g><rect width="1"/></g></svg>"#).unwrap(),
+            SVG::parse_svg_string(r#"<svg><g><rect width="2"/></g></svg>"#).unwrap(),
+            SVG::parse_svg_string(r#"<svg><g><rect width="2"/></g><circle r="1"/></svg>"#).unwrap(),
+        ];
+        let config = Config::default();
+
+        // Act
+        let (svgs, _diffs, _view_box, lifetimes) =
+            diffs_with_lifetimes(&frames, None, &config).unwrap();
+
+        // Test, the rect is identical in the second diff and keeps the id it got in the first
+        let rect =
+            frames[1].child_positions(frames[1].child_positions(frames[1].root_position())[0])[0];
+        let id = svgs[0].at_pos(rect).value().1.clone();
+        assert!(id.is_some());
+        assert_eq!(svgs[1].at_pos(rect).value().1, &id);
+        for lifetime in lifetimes {
+            for frame in lifetime.first_frame..=lifetime.last_frame.min(svgs.len() - 1) {
+                assert!(ids_of(&svgs[frame]).contains(&Some(lifetime.id.clone())));
+            }
+        }
+    }
+
+    #[test]
+    fn ids_in_other_attribute() {
+        // setup
+        let origin = r#"<svg><rect id="logo" width="1"/></svg>"#.to_string();
+        let target = r#"<svg><rect id="brand" width="2"/></svg>"#.to_string();
+        let config = Config {
+            ids: IdOptions {
+                attribute: "data-svgdiff-id".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // Act
+        let (svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the author id is kept and changed like any other attribute
+        assert!(svgs[0].contains(r#"id="logo""#));
+        assert!(svgs[0].contains("data-svgdiff-id="));
+        assert_eq!(diffs[0].len(), 1);
+        let change = serde_json::to_value(&diffs[0][0]).unwrap();
+        assert_ne!(change["id"], "logo");
+        let changed: Vec<&str> = change["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["prop"].as_str().unwrap())
+            .collect();
+        assert_eq!(changed, vec!["id", "width"]);
+    }
+
+    #[test]
+    fn colliding_ids_and_references() {
+        // setup, the matched gradient gets the id of the origin gradient,
+        // which is also the id of an added circle
+        let origin = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <linearGradient id="a" data-node="g"><stop offset="0"/></linearGradient>
+          <rect data-node="r" fill="url(#a)" width="1"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <linearGradient id="b" data-node="g"><stop offset="0"/></linearGradient>
+          <circle id="a" r="1"/>
+          <rect data-node="r" fill="url(#b)" width="2"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let config = Config::default();
+
+        // Act
+        let (_, target_with_ids, diff) =
+            diff_with_strategy(&origin, &target, &config, &ByDataNode).unwrap();
+
+        // Test, the circle gets a new id and the fill follows the gradient
+        let printed = print_svg_with_id_attribute(&target_with_ids, None, "id");
+        assert!(printed.contains(r#"fill="url(#a)""#));
+        assert_eq!(printed.matches(r#"id="a""#).count(), 1);
+        let steps = serde_json::to_value(&diff).unwrap();
+        let steps = steps.as_array().unwrap();
+        let add = steps.iter().find(|s| s["action"] == "add").unwrap();
+        assert_ne!(add["id"], "a");
+        let changes: Vec<&serde_json::Value> =
+            steps.iter().filter(|s| s["action"] == "change").collect();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["changes"].as_array().unwrap().len(), 1);
+        assert_eq!(changes[0]["changes"][0]["prop"], "width");
+    }
+
+    /// Matches the elements with the same `data-node` attribute.
+    struct ByDataNode;
+
+    impl MatchingStrategy for ByDataNode {
+        fn find_matches(&self, origin: &SVG, target: &SVG) -> Vec<(usize, usize)> {
+            let mut res = Vec::new();
+            for o_index in origin.positions() {
+                let node = origin.tag(o_index).attribute("data-node");
+                if node.is_none() {
+                    continue;
+                }
+                if let Some(t_index) = target
+                    .positions()
+                    .into_iter()
+                    .find(|t_index| target.tag(*t_index).attribute("data-node") == node)
+                {
+                    res.push((o_index, t_index));
+                }
+            }
+            res
+        }
+
+        fn name(&self) -> &'static str {
+            "data_node"
+        }
+    }
+
+    #[test]
+    fn custom_strategy() {
+        // setup
+        let origin = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <text data-node="a">x</text>
+          <text data-node="b">y</text>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <text data-node="b">x</text>
+          <text data-node="a">y</text>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let config = Config::default();
+
+        // Act
+        let (_, _, diff) = diff_with_strategy(&origin, &target, &config, &ByDataNode).unwrap();
+
+        // Test, the nodes keep their identity and their texts change
+        assert_eq!(diff.iter().filter(|d| d.is_text_change()).count(), 2);
+        assert!(!diff.iter().any(|d| d.is_add() || d.is_remove()));
+    }
+
+    #[test]
+    fn strategy_and_keys() {
+        let svg = |keys: [&str; 2]| {
+            SVG::parse_svg_string(&format!(
+                r#"<svg><text data-node="a" data-key="{}">x</text><text data-node="b" data-key="{}">y</text></svg>"#,
+                keys[0], keys[1]
+            ))
+            .unwrap()
+        };
+        let mut config = Config::default();
+        config.matching.key_attributes = vec!["data-key".to_string()];
+
+        // The keys agree with the strategy, the strategy is reported
+        let (origin, target) = (svg(["1", "2"]), svg(["1", "2"]));
+        let mut g = MatchingIdGenerator::new(&config.ids);
+        let (_, _, _, report) = diff_impl(
+            &origin,
+            &target,
+            &config,
+            Some(&ByDataNode as &dyn MatchingStrategy),
+            &mut g,
+            true,
+        )
+        .unwrap();
+        let rules: Vec<String> = report.unwrap().pairs.into_iter().map(|p| p.rule).collect();
+        assert_eq!(rules, vec!["root", "data_node", "data_node"]);
+
+        // The keys match the elements the other way round
+        let target = svg(["2", "1"]);
+        let error = diff_with_strategy(&origin, &target, &config, &ByDataNode).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidMatch(..)));
+    }
+
+    /// Matches an element twice.
+    struct Duplicate;
+
+    impl MatchingStrategy for Duplicate {
+        fn find_matches(&self, _origin: &SVG, _target: &SVG) -> Vec<(usize, usize)> {
+            vec![(1, 1), (1, 2)]
+        }
+    }
+
+    #[test]
+    fn invalid_strategy() {
+        let svg =
+            SVG::parse_svg_string(r#"<svg><rect width="1"/><rect width="2"/></svg>"#).unwrap();
+        assert!(diff_with_strategy(&svg, &svg, &Config::default(), &Duplicate).is_err());
+    }
+
+    /// Matches a group with a text.
+    struct GroupToText;
+
+    impl MatchingStrategy for GroupToText {
+        fn find_matches(&self, _origin: &SVG, _target: &SVG) -> Vec<(usize, usize)> {
+            vec![(1, 2)]
+        }
+    }
+
+    #[test]
+    fn strategy_with_other_tag_name() {
+        let svg = SVG::parse_svg_string(r#"<svg><g/><text>a</text></svg>"#).unwrap();
+        let error = diff_with_strategy(&svg, &svg, &Config::default(), &GroupToText).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidMatch(1, 2, _)));
+    }
+
+    #[test]
+    fn matching_report() {
+        // setup
+        let origin = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <rect data-key="a" x="0"/>
+          <circle cx="5" r="5"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <rect data-key="a" x="10"/>
+          <circle cx="5" r="6"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.matching.key_attributes = vec!["data-key".to_string()];
+        config.matching.priorities = vec!["all".to_string()];
+
+        // Act
+        let (_origin, _target, _diffs, report) =
+            diff_with_report(&origin, &target, &config).unwrap();
+
+        // Test
+        let rules: Vec<(&str, Vec<usize>)> = report
+            .pairs
+            .iter()
+            .map(|p| (p.rule.as_str(), p.target_path.clone()))
+            .collect();
+        assert_eq!(rules, vec![("root", vec![]), ("key", vec![0])]);
+        assert_eq!(report.unmatched.len(), 2);
+        let removed = report.unmatched.iter().find(|u| u.in_origin).unwrap();
+        let added = report.unmatched.iter().find(|u| !u.in_origin).unwrap();
+        assert_eq!(removed.path, vec![1]);
+        assert_eq!(removed.near_misses.len(), 1);
+        assert_eq!(removed.near_misses[0].id, added.id);
+        assert!(removed.near_misses[0].score > 0.0);
+        assert!(serde_json::to_string(&report).is_ok());
+    }
+
+    #[test]
+    fn matching_report_with_scoring() {
+        let origin = SVG::parse_svg_string(r#"<svg><circle cx="5" r="5"/></svg>"#).unwrap();
+        let target = SVG::parse_svg_string(r#"<svg><circle cx="5" r="6"/></svg>"#).unwrap();
+        let mut config = Config::default();
+        config.matching.priorities = vec!["all".to_string()];
+        config.matching.scoring = Some(SimilarityScoring {
+            min_score: 1.0,
+            attr_weight: 1.0,
+            text_weight: 0.0,
+            distance_weight: 0.0,
+            ..Default::default()
+        });
+
+        let (_origin, _target, _diffs, report) =
+            diff_with_report(&origin, &target, &config).unwrap();
+
+        // Only the attributes count, one of the three is equal
+        let removed = report.unmatched.iter().find(|u| u.in_origin).unwrap();
+        assert_eq!(removed.near_misses.len(), 1);
+        assert!((removed.near_misses[0].score - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matching_report_with_ignored_content() {
+        // setup
+        let origin = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <metadata>1</metadata>
+          <rect data-key="a" x="0"/>
+          <circle cx="5" r="5"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <metadata>2</metadata>
+          <rect data-key="a" x="10"/>
+          <circle cx="5" r="6"/>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.ignore.elements = vec![Selector::from_str("metadata").unwrap()];
+        config.matching.key_attributes = vec!["data-key".to_string()];
+        config.matching.priorities = vec!["all".to_string()];
+
+        // Act
+        let (_origin, _target, _diffs, report) =
+            diff_with_report(&origin, &target, &config).unwrap();
+
+        // Test, the paths count the ignored metadata
+        let pair = report.pairs.iter().find(|p| p.rule == "key").unwrap();
+        assert_eq!(pair.origin_path, vec![1]);
+        assert_eq!(pair.target_path, vec![1]);
+        let removed = report.unmatched.iter().find(|u| u.in_origin).unwrap();
+        assert_eq!(removed.path, vec![2]);
+        assert_eq!(removed.near_misses[0].path, vec![2]);
+    }
+
+    #[test]
+    fn label_of_distant_sibling() {
+        // setup
+        let origin = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <rect width="10"/><circle r="1"/><text>Alice</text>
+          <rect width="20"/><circle r="1"/><text>Bob</text>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r###"
+        <svg>
+          <rect width="20"/><circle r="1"/><text>Bobby</text>
+          <rect width="10"/><circle r="1"/><text>Alicia</text>
+        </svg>
+        "###,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        let mut label = MatchingRule::new_all_without_subtrees_rule();
+        label.name = "label_of_rect".to_string();
+        label.apply_to_tags = Some(["text".to_string()].into());
+        label.attr = None;
+        label.include_text = false;
+        label.sibling = Some(SiblingRule {
+            offset: -2,
+            rule: "all".to_string(),
+        });
+        config.matching.rules.push(label);
+        config
+            .matching
+            .priorities
+            .insert(0, "label_of_rect".to_string());
+
+        // Act
+        let (_origin, _target, _diffs, report) =
+            diff_with_report(&origin, &target, &config).unwrap();
+
+        // Test, the texts are matched by the rect two before them
+        let alice = report
+            .pairs
+            .iter()
+            .find(|p| p.origin_path == vec![2])
+            .unwrap();
+        assert_eq!(alice.target_path, vec![5]);
+        assert_eq!(alice.rule, "label_of_rect");
+    }
+
+    #[test]
+    fn graph_edges() {
+        // setup
+        let origin = r###"
+        <svg>
+          <g class="node"><title>a</title><ellipse cx="0" cy="0"/></g>
+          <g class="node"><title>b</title><ellipse cx="0" cy="50"/></g>
+          <g class="edge"><title>a&#45;&gt;b</title><path d="M0,0 L0,50"/></g>
+          <g class="node"><title>c</title><ellipse cx="50" cy="50"/></g>
+          <g class="edge"><title>a&#45;&gt;c</title><path d="M0,0 L50,50"/></g>
+        </svg>
+        "###
+        .to_string();
+        let target = r###"
+        <svg>
+          <g class="node"><title>a</title><ellipse cx="0" cy="0"/></g>
+          <g class="node"><title>c</title><ellipse cx="0" cy="50"/></g>
+          <g class="edge"><title>a&#45;&gt;c</title><path d="M0,0 L0,50"/></g>
+          <g class="node"><title>b</title><ellipse cx="50" cy="50"/></g>
+          <g class="edge"><title>a&#45;&gt;b</title><path d="M0,0 L50,50"/></g>
+        </svg>
+        "###
+        .to_string();
+        let mut config = Config::default();
+        config.matching.graph = Some(Default::default());
+
+        // Act
+        let (_svgs, diffs) = diff_from_strings(&[origin, target], &config).unwrap();
+
+        // Test, the nodes and edges keep their ids and are changed, not added
+        assert!(!diffs[0].iter().any(|d| d.is_add() || d.is_remove()));
+        assert!(!diffs[0].iter().any(|d| d.is_text_change()));
+        assert_eq!(diffs[0].iter().filter(|d| d.is_change()).count(), 4);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        // setup
+        let origin = r###"
+        <svg>
+          <rect data-key="a"/>
+          <rect data-key="a"/>
+        </svg>
+        "###
+        .to_string();
+        let target = origin.clone();
+        let mut config = Config::default();
+        config.matching.key_attributes = vec!["data-key".to_string()];
+
+        // Act
+        let res = diff_from_strings(&[origin, target], &config);
+
+        // Test
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn conflicting_keys() {
+        let diff = |origin: &str, target: &str, keys: &[&str]| {
+            let mut config = Config::default();
+            config.matching.key_attributes = keys.iter().map(|k| k.to_string()).collect();
+            diff_from_strings(&[origin.to_string(), target.to_string()], &config)
+        };
+
+        // A group and a text with the same key
+        let res = diff(
+            r#"<svg><g data-key="a"/></svg>"#,
+            r#"<svg><text data-key="a">a</text></svg>"#,
+            &["data-key"],
+        );
+        assert!(matches!(
+            res.unwrap_err().kind(),
+            ErrorKind::InvalidMatch(..)
+        ));
+
+        // A rect and a path can be turned into each other
+        let res = diff(
+            r#"<svg><rect data-key="a" width="1"/></svg>"#,
+            r#"<svg><path data-key="a" d="M 0 0 h 1"/></svg>"#,
+            &["data-key"],
+        );
+        assert!(res.is_ok());
+
+        // The keys of the rect match different elements
+        let res = diff(
+            r#"<svg><rect data-key="a" data-name="x"/><rect data-key="b"/></svg>"#,
+            r#"<svg><rect data-key="a"/><rect data-key="b" data-name="x"/></svg>"#,
+            &["data-key", "data-name"],
+        );
+        assert!(matches!(
+            res.unwrap_err().kind(),
+            ErrorKind::InvalidMatch(..)
+        ));
+    }
 }
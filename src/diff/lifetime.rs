@@ -0,0 +1,113 @@
+//! The identity of elements across a sequence of SVGs (frames).
+//!
+//! The ids of the target of a diff are carried forward to the origin of the
+//! next diff, so an element keeps its id as long as it is matched.
+
+use flange_flat_tree::{Subtree, Tree};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::svg_data::SVGWithIDs;
+
+/// The frames in which an element exists (with the same id).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ElementLifetime {
+    pub id: String,
+    /// The index of the first SVG (frame) with the element.
+    pub first_frame: usize,
+    /// The index of the last SVG (frame) with the element.
+    pub last_frame: usize,
+}
+
+/// The ids of all elements of the svg, by position.
+pub(crate) fn ids_of(svg: &SVGWithIDs) -> Vec<Option<String>> {
+    (0..svg.node_count())
+        .map(|pos| svg.at_pos(pos).value().1.clone())
+        .collect()
+}
+
+/// The ids of the elements of the target, known after diffing it with the origin.
+///
+/// These are the ids the target got, and the ids the elements had in the
+/// origin (`origin_ids`) for the elements below identical elements
+/// (which get no id of their own in a diff).
+pub(crate) fn carry_forward(
+    origin: &SVGWithIDs,
+    target: &SVGWithIDs,
+    origin_ids: &[Option<String>],
+) -> Vec<Option<String>> {
+    let mut res = ids_of(target);
+    let origin_by_id: HashMap<String, usize> = origin_ids
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, id)| id.clone().map(|id| (id, pos)))
+        .collect();
+    for t_index in 0..target.node_count() {
+        let o_index = target
+            .at_pos(t_index)
+            .value()
+            .1
+            .as_ref()
+            .and_then(|id| origin_by_id.get(id));
+        if let Some(o_index) = o_index {
+            carry_identical(origin, target, *o_index, t_index, origin_ids, &mut res);
+        }
+    }
+    res
+}
+
+/// Gives the descendants of the target element the ids of the descendants of
+/// the origin element, if they are not diffed (the elements are identical).
+fn carry_identical(
+    origin: &SVGWithIDs,
+    target: &SVGWithIDs,
+    o_index: usize,
+    t_index: usize,
+    origin_ids: &[Option<String>],
+    res: &mut Vec<Option<String>>,
+) {
+    let origin_children = origin.at_pos(o_index).children();
+    let target_children = target.at_pos(t_index).children();
+    if origin_children.len() != target_children.len()
+        || target_children.iter().any(|c| c.value().1.is_some())
+    {
+        return;
+    }
+    for (o_child, t_child) in origin_children.iter().zip(target_children.iter()) {
+        res[t_child.get_pos()] = origin_ids[o_child.get_pos()].clone();
+        carry_identical(
+            origin,
+            target,
+            o_child.get_pos(),
+            t_child.get_pos(),
+            origin_ids,
+            res,
+        );
+    }
+}
+
+impl ElementLifetime {
+    /// The lifetimes of all ids in the frames (the ids of the elements of every frame),
+    /// sorted by the first frame and the id.
+    pub(crate) fn from_frames(frames: &[Vec<Option<String>>]) -> Vec<ElementLifetime> {
+        let mut lifetimes: BTreeMap<&String, (usize, usize)> = BTreeMap::new();
+        for (frame, ids) in frames.iter().enumerate() {
+            for id in ids.iter().flatten() {
+                lifetimes
+                    .entry(id)
+                    .and_modify(|l| l.1 = frame)
+                    .or_insert((frame, frame));
+            }
+        }
+        let mut res: Vec<ElementLifetime> = lifetimes
+            .into_iter()
+            .map(|(id, (first_frame, last_frame))| ElementLifetime {
+                id: id.clone(),
+                first_frame,
+                last_frame,
+            })
+            .collect();
+        res.sort_by(|a, b| a.first_frame.cmp(&b.first_frame).then(a.id.cmp(&b.id)));
+        res
+    }
+}
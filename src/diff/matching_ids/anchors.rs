@@ -0,0 +1,148 @@
+//! Anchors are pairs of elements that are known to match,
+//! before any matching rules are applied.
+//!
+//! Anchored elements are only ever matched with each other,
+//! wherever they are in the SVGs.
+
+use crate::errors::*;
+use crate::svg_data::{is_basic_shape, Tag};
+use crate::SVG;
+use error_chain::bail;
+use std::collections::HashMap;
+
+/// The name reported for matches by the `key_attributes`.
+pub(crate) const KEY_RULE: &str = "key";
+
+/// Whether the elements can be anchored. Elements with different tag names
+/// can only be turned into each other if both are basic shapes (see `DiffStep::change_shape`).
+pub(crate) fn can_match(origin: &Tag, target: &Tag) -> bool {
+    origin.name == target.name || (is_basic_shape(&origin.name) && is_basic_shape(&target.name))
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Anchors {
+    origin_to_target: HashMap<usize, usize>,
+    target_to_origin: HashMap<usize, usize>,
+    /// Why the elements are anchored, by the origin index.
+    rules: HashMap<usize, &'static str>,
+}
+
+impl Anchors {
+    /// Anchors the elements in `origin` and `target` with equal keys.
+    ///
+    /// # Errors
+    ///
+    /// If a key is used by more than one element in the same SVG, if elements
+    /// with different tag names (that are not both basic shapes) have the same key,
+    /// or if the keys of an element match different elements.
+    pub fn from_keys(origin: &SVG, target: &SVG, key_attributes: &[String]) -> Result<Anchors> {
+        let mut res = Anchors::default();
+        if key_attributes.is_empty() {
+            return Ok(res);
+        }
+        let origin_keys = origin.elements_by_key(key_attributes)?;
+        let target_keys = target.elements_by_key(key_attributes)?;
+        let mut keys: Vec<&(String, String)> = origin_keys.keys().collect();
+        keys.sort();
+        for key in keys {
+            if let Some(t_index) = target_keys.get(key) {
+                let o_index = origin_keys[key];
+                if !can_match(origin.tag(o_index), target.tag(*t_index)) {
+                    bail!(ErrorKind::InvalidMatch(
+                        o_index,
+                        *t_index,
+                        format!(
+                            "the {} {} is used by elements with different tag names",
+                            key.0, key.1
+                        )
+                    ));
+                }
+                if res.target_of(o_index) != Some(*t_index)
+                    && !res.insert(o_index, *t_index, KEY_RULE)
+                {
+                    bail!(ErrorKind::InvalidMatch(
+                        o_index,
+                        *t_index,
+                        format!(
+                            "the {} {} matches an element that is matched by another key",
+                            key.0, key.1
+                        )
+                    ));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Anchors a pair of elements, if none of them is already anchored.
+    /// `rule` names the reason for the match (see `MatchingState::matched_by`).
+    pub fn insert(&mut self, origin_index: usize, target_index: usize, rule: &'static str) -> bool {
+        if self.origin_to_target.contains_key(&origin_index)
+            || self.target_to_origin.contains_key(&target_index)
+        {
+            return false;
+        }
+        self.origin_to_target.insert(origin_index, target_index);
+        self.target_to_origin.insert(target_index, origin_index);
+        self.rules.insert(origin_index, rule);
+        true
+    }
+
+    /// Adds the pairs of `other`, that don't conflict with the pairs so far.
+    pub fn extend(&mut self, other: &Anchors) {
+        for (origin_index, target_index) in other.pairs() {
+            self.insert(origin_index, target_index, other.rule_of(origin_index));
+        }
+    }
+
+    /// Adds the pairs of `other`, the pairs that are already anchored are kept as they are.
+    ///
+    /// # Errors
+    ///
+    /// If one of the elements of a pair is already anchored with another element.
+    pub fn merge(&mut self, other: &Anchors) -> Result<()> {
+        for (origin_index, target_index) in other.pairs() {
+            if self.target_of(origin_index) == Some(target_index) {
+                continue;
+            }
+            if !self.insert(origin_index, target_index, other.rule_of(origin_index)) {
+                let matched_by = match self.origin_of(target_index) {
+                    Some(existing) if self.target_of(origin_index).is_none() => {
+                        self.rule_of(existing)
+                    }
+                    _ => self.rule_of(origin_index),
+                };
+                bail!(ErrorKind::InvalidMatch(
+                    origin_index,
+                    target_index,
+                    format!("one of the elements is already matched by {}", matched_by)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The reason the element is anchored.
+    pub fn rule_of(&self, origin_index: usize) -> &'static str {
+        self.rules.get(&origin_index).cloned().unwrap_or(KEY_RULE)
+    }
+
+    pub fn target_of(&self, origin_index: usize) -> Option<usize> {
+        self.origin_to_target.get(&origin_index).cloned()
+    }
+
+    pub fn origin_of(&self, target_index: usize) -> Option<usize> {
+        self.target_to_origin.get(&target_index).cloned()
+    }
+
+    /// All anchored pairs, sorted by the origin index.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut res: Vec<(usize, usize)> = self
+            .origin_to_target
+            .iter()
+            .map(|(o, t)| (*o, *t))
+            .collect();
+        res.sort();
+        res
+    }
+}
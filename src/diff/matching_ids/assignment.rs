@@ -0,0 +1,143 @@
+//! Solving of the assignment problem (Hungarian algorithm).
+//!
+//! Used to find the pairs of elements with the best total similarity,
+//! instead of just taking the first pairs that match.
+
+/// Cost for pairs that are not allowed to be assigned.
+/// Large enough that the algorithm always prefers more allowed pairs.
+const FORBIDDEN: f64 = 1e9;
+
+/// Finds the assignment between rows and columns with the highest total score.
+///
+/// `scores[row][column]` is the score of the pair or `None` if the pair
+/// is not allowed. The result contains the assigned (row, column) pairs,
+/// first maximizing the number of pairs and then the total score.
+pub(crate) fn optimal_assignment(scores: &[Vec<Option<f64>>]) -> Vec<(usize, usize)> {
+    let rows = scores.len();
+    let columns = scores.first().map(|r| r.len()).unwrap_or(0);
+    if rows == 0 || columns == 0 {
+        return Vec::new();
+    }
+    let max_score = scores
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(0.0_f64, |a, b| a.max(*b));
+    let cost = |row: usize, column: usize| {
+        scores[row][column]
+            .map(|s| max_score - s)
+            .unwrap_or(FORBIDDEN)
+    };
+    // The algorithm needs at most as many rows as columns
+    let pairs: Vec<(usize, usize)> = if rows <= columns {
+        let matrix: Vec<Vec<f64>> = (0..rows)
+            .map(|r| (0..columns).map(|c| cost(r, c)).collect())
+            .collect();
+        hungarian(&matrix).into_iter().enumerate().collect()
+    } else {
+        let matrix: Vec<Vec<f64>> = (0..columns)
+            .map(|c| (0..rows).map(|r| cost(r, c)).collect())
+            .collect();
+        hungarian(&matrix)
+            .into_iter()
+            .enumerate()
+            .map(|(c, r)| (r, c))
+            .collect()
+    };
+    pairs
+        .into_iter()
+        .filter(|(r, c)| scores[*r][*c].is_some())
+        .collect()
+}
+
+/// Hungarian algorithm for a cost matrix with not more rows than columns.
+/// Returns for every row the assigned column, minimizing the total cost.
+#[allow(clippy::needless_range_loop)]
+fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost[0].len();
+    // Potentials and the matching, 1-based with 0 as a virtual start
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; m + 1];
+    let mut p = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if !used[j] {
+                    let current = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                    if current < min_v[j] {
+                        min_v[j] = current;
+                        way[j] = j0;
+                    }
+                    if min_v[j] < delta {
+                        delta = min_v[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+        // Augment along the found path
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+    let mut res = vec![0; n];
+    for j in 1..=m {
+        if p[j] != 0 {
+            res[p[j] - 1] = j - 1;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn best_total_score() {
+        // The first-fit pairing (0, 0), (1, 1) has a worse total score
+        let scores = vec![vec![Some(0.6), Some(0.9)], vec![Some(0.9), Some(0.1)]];
+        let mut pairs = optimal_assignment(&scores);
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn forbidden_pairs() {
+        let scores = vec![
+            vec![None, Some(0.2), None],
+            vec![None, Some(0.9), None],
+            vec![Some(0.5), None, None],
+        ];
+        let mut pairs = optimal_assignment(&scores);
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 1), (2, 0)]);
+    }
+}
@@ -0,0 +1,135 @@
+//! Makes the ids of the elements unique in both SVGs.
+//!
+//! Matched elements take the id of the origin element (see `MatchingIdGenerator::next`),
+//! which can also be used by another element in the target. Generated ids can
+//! also be the same as the ids of elements that don't get a matching id.
+
+use crate::diff::matching_ids::{generator::MatchingIdGenerator, matching_state::MatchingState};
+use crate::svg_data::SVGWithMatchingState;
+use crate::SVG;
+use flange_flat_tree::Tree;
+use std::collections::{HashMap, HashSet};
+
+/// The origin and target index, that identify the element(s) with a state.
+type Identity = (Option<usize>, Option<usize>);
+
+fn identity(state: &MatchingState) -> Identity {
+    (state.get_origin_index(), state.get_target_index())
+}
+
+/// The ids (in `id_attribute`) of the elements without a state, these are printed as they are.
+/// Ids used by more than one element of the same SVG are logged.
+fn printed_ids(svg: &SVG, states: &SVGWithMatchingState, id_attribute: &str) -> HashSet<String> {
+    let mut res = HashSet::new();
+    for pos in 0..svg.tags.node_count() {
+        if states.get_flange(pos).is_some() {
+            continue;
+        }
+        if let Some(id) = svg.tag(pos).attribute(id_attribute) {
+            if !res.insert(id.clone()) {
+                log::warn!("the id {} is used by more than one element", id);
+            }
+        }
+    }
+    res
+}
+
+/// Gives new ids to the elements whose id is already used by another element,
+/// in the origin or the target (the first element in the origin keeps it).
+///
+/// The references to the renamed elements are rewritten when they are printed
+/// (see `Addressing`).
+pub(crate) fn resolve_collisions<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    origin_states: SVGWithMatchingState<'a>,
+    target_states: SVGWithMatchingState<'a>,
+    id_attribute: &str,
+    g: &mut MatchingIdGenerator,
+) -> (SVGWithMatchingState<'a>, SVGWithMatchingState<'a>) {
+    let mut taken = printed_ids(origin, &origin_states, id_attribute);
+    taken.extend(printed_ids(target, &target_states, id_attribute));
+
+    // The matched and removed elements, then the added elements
+    let mut states = Vec::new();
+    for pos in origin.positions() {
+        states.extend(origin_states.get_flange(pos).clone());
+    }
+    for pos in target.positions() {
+        states.extend(
+            target_states
+                .get_flange(pos)
+                .clone()
+                .filter(|s| s.get_origin_index().is_none()),
+        );
+    }
+
+    let mut renamed: HashMap<Identity, String> = HashMap::new();
+    for state in states {
+        let id = state.get_id();
+        if taken.insert(id.clone()) {
+            continue;
+        }
+        let mut new_id = g.next(None, None, None);
+        while !taken.insert(new_id.clone()) {
+            new_id = g.next(None, None, None);
+        }
+        log::info!("the id {} is used more than once, using {}", id, new_id);
+        renamed.insert(identity(&state), new_id);
+    }
+    if renamed.is_empty() {
+        return (origin_states, target_states);
+    }
+    let rename = |state: &Option<MatchingState>| {
+        state.as_ref().map(|s| match renamed.get(&identity(s)) {
+            Some(id) => s.with_id(id.clone()),
+            None => s.clone(),
+        })
+    };
+    (
+        origin_states.replace_map_flange(|s| rename(s.1)),
+        target_states.replace_map_flange(|s| rename(s.1)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::IdOptions;
+
+    #[test]
+    fn colliding_ids() {
+        let origin = SVG::parse_svg_string(r#"<svg><rect id="a"/></svg>"#).unwrap();
+        let target = SVG::parse_svg_string(r#"<svg><circle id="a"/></svg>"#).unwrap();
+        let mut g = MatchingIdGenerator::new(&IdOptions::default());
+        let rect = origin.child_positions(origin.root_position())[0];
+        let circle = target.child_positions(target.root_position())[0];
+        let mut origin_ids = vec![None; origin.tags.node_count()];
+        let mut target_ids = vec![None; target.tags.node_count()];
+        origin_ids[rect] = Some(MatchingState::new_unmatched(
+            rect,
+            true,
+            &mut g,
+            Some("a".to_string()),
+        ));
+        target_ids[circle] = Some(MatchingState::new_unmatched(
+            circle,
+            false,
+            &mut g,
+            Some("a".to_string()),
+        ));
+        let (origin_states, target_states) = resolve_collisions(
+            &origin,
+            &target,
+            origin.with_matching_states(origin_ids),
+            target.with_matching_states(target_ids),
+            "id",
+            &mut g,
+        );
+        // The removed rect keeps its id, the added circle gets a new one
+        let id =
+            |states: &SVGWithMatchingState, pos| states.get_flange(pos).as_ref().unwrap().get_id();
+        assert_eq!(id(&origin_states, rect), "a");
+        assert_ne!(id(&target_states, circle), "a");
+    }
+}
@@ -0,0 +1,258 @@
+//! Matching by a minimum-cost tree edit script (see `Algorithm::TreeEditDistance`).
+//!
+//! The edit distance is computed with the algorithm of Zhang and Shasha,
+//! the matched elements are the ones that are kept (and maybe changed) by the script.
+
+use crate::config::EditCosts;
+use crate::diff::matching_ids::{
+    anchors::Anchors, from_pairs::matching_states_from_pairs, generator::MatchingIdGenerator,
+};
+use crate::svg_data::{is_basic_shape, SVGWithMatchingState, Tag};
+use crate::SVG;
+use flange_flat_tree::{Subtree, Tree};
+use std::collections::HashMap;
+
+/// The name reported for elements matched by the edit script.
+pub(crate) const EDIT_DISTANCE_RULE: &str = "edit_distance";
+
+/// The elements of an SVG in post-order (children before their parent),
+/// as needed by the Zhang-Shasha algorithm. Indices start at 1.
+struct PostOrder<'a> {
+    tags: Vec<&'a Tag>,
+    /// The positions of the elements in the SVG.
+    positions: Vec<usize>,
+    /// The index of the leftmost leaf below every element.
+    leftmost: Vec<usize>,
+    /// The elements that have no left sibling on the path to their leftmost leaf.
+    keyroots: Vec<usize>,
+}
+
+impl<'a> PostOrder<'a> {
+    fn new(svg: &'a SVG) -> PostOrder<'a> {
+        let mut res = PostOrder {
+            tags: vec![svg.tags.root().value()],
+            positions: vec![svg.tags.root().get_pos()],
+            leftmost: vec![0],
+            keyroots: Vec::new(),
+        };
+        res.visit(svg, svg.tags.root().get_pos());
+        let mut last_with_leftmost = HashMap::new();
+        for index in 1..res.tags.len() {
+            last_with_leftmost.insert(res.leftmost[index], index);
+        }
+        res.keyroots = last_with_leftmost.into_values().collect();
+        res.keyroots.sort();
+        res
+    }
+
+    fn visit(&mut self, svg: &'a SVG, pos: usize) -> usize {
+        let mut leftmost = None;
+        for &child in svg.tags.get_nav().children(pos).iter() {
+            let child_index = self.visit(svg, child);
+            leftmost.get_or_insert(self.leftmost[child_index]);
+        }
+        self.tags.push(svg.tags.at_pos(pos).value());
+        self.positions.push(pos);
+        let index = self.tags.len() - 1;
+        self.leftmost.push(leftmost.unwrap_or(index));
+        index
+    }
+
+    fn len(&self) -> usize {
+        self.tags.len() - 1
+    }
+}
+
+/// Computes the edit distance between two SVGs and the elements it keeps.
+struct EditDistance<'a> {
+    origin: PostOrder<'a>,
+    target: PostOrder<'a>,
+    costs: &'a EditCosts,
+    /// The distance between all pairs of subtrees.
+    tree_dist: Vec<Vec<f64>>,
+}
+
+/// Whether two costs are the same (apart from rounding errors).
+fn same_cost(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+}
+
+impl<'a> EditDistance<'a> {
+    fn new(origin: &'a SVG, target: &'a SVG, costs: &'a EditCosts) -> EditDistance<'a> {
+        let origin = PostOrder::new(origin);
+        let target = PostOrder::new(target);
+        let tree_dist = vec![vec![0.0; target.len() + 1]; origin.len() + 1];
+        let mut res = EditDistance {
+            origin,
+            target,
+            costs,
+            tree_dist,
+        };
+        for i in res.origin.keyroots.clone() {
+            for j in res.target.keyroots.clone() {
+                res.forest_dist(i, j);
+            }
+        }
+        res
+    }
+
+    /// The cost of changing the origin element `i` into the target element `j`.
+    fn change_cost(&self, i: usize, j: usize) -> f64 {
+        // The roots are always matched
+        if i == self.origin.len() && j == self.target.len() {
+            return 0.0;
+        }
+        let (a, b) = (self.origin.tags[i], self.target.tags[j]);
+        let mut cost = 0.0;
+        if a.name != b.name {
+            if !(is_basic_shape(&a.name) && is_basic_shape(&b.name)) {
+                return f64::INFINITY;
+            }
+            cost += self.costs.relabel;
+        }
+        if a.text != b.text {
+            cost += self.costs.text;
+        }
+        let changed_attributes = a
+            .args
+            .iter()
+            .filter(|(name, value)| b.args.get(*name) != Some(value))
+            .count()
+            + b.args
+                .keys()
+                .filter(|name| !a.args.contains_key(*name))
+                .count();
+        cost + changed_attributes as f64 * self.costs.attribute
+    }
+
+    /// The distances between the forests of the subtrees `i` and `j`
+    /// (indexed relative to their leftmost leaves, 0 is the empty forest).
+    /// Updates the distances of the subtrees on the leftmost paths of `i` and `j`.
+    fn forest_dist(&mut self, i: usize, j: usize) -> Vec<Vec<f64>> {
+        let (li, lj) = (self.origin.leftmost[i], self.target.leftmost[j]);
+        let mut fd = vec![vec![0.0; j - lj + 2]; i - li + 2];
+        for x in 1..fd.len() {
+            fd[x][0] = fd[x - 1][0] + self.costs.delete;
+        }
+        for y in 1..fd[0].len() {
+            fd[0][y] = fd[0][y - 1] + self.costs.insert;
+        }
+        for x in 1..fd.len() {
+            let i1 = li + x - 1;
+            for y in 1..fd[0].len() {
+                let j1 = lj + y - 1;
+                let edited =
+                    (fd[x - 1][y] + self.costs.delete).min(fd[x][y - 1] + self.costs.insert);
+                if self.origin.leftmost[i1] == li && self.target.leftmost[j1] == lj {
+                    fd[x][y] = edited.min(fd[x - 1][y - 1] + self.change_cost(i1, j1));
+                    self.tree_dist[i1][j1] = fd[x][y];
+                } else {
+                    let (px, py) = (self.origin.leftmost[i1] - li, self.target.leftmost[j1] - lj);
+                    fd[x][y] = edited.min(fd[px][py] + self.tree_dist[i1][j1]);
+                }
+            }
+        }
+        fd
+    }
+
+    /// The pairs of elements (post-order indices) kept by a minimum-cost edit script.
+    /// Of equally good scripts, those keeping more elements are preferred.
+    fn mapping(&mut self) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        let mut subtrees = vec![(self.origin.len(), self.target.len())];
+        while let Some((i, j)) = subtrees.pop() {
+            let fd = self.forest_dist(i, j);
+            let (li, lj) = (self.origin.leftmost[i], self.target.leftmost[j]);
+            let (mut x, mut y) = (i - li + 1, j - lj + 1);
+            while x > 0 || y > 0 {
+                if x > 0 && y > 0 {
+                    let (i1, j1) = (li + x - 1, lj + y - 1);
+                    if self.origin.leftmost[i1] == li && self.target.leftmost[j1] == lj {
+                        if same_cost(fd[x - 1][y - 1] + self.change_cost(i1, j1), fd[x][y]) {
+                            res.push((i1, j1));
+                            x -= 1;
+                            y -= 1;
+                            continue;
+                        }
+                    } else {
+                        let (px, py) =
+                            (self.origin.leftmost[i1] - li, self.target.leftmost[j1] - lj);
+                        if same_cost(fd[px][py] + self.tree_dist[i1][j1], fd[x][y]) {
+                            subtrees.push((i1, j1));
+                            x = px;
+                            y = py;
+                            continue;
+                        }
+                    }
+                }
+                if x > 0 && same_cost(fd[x - 1][y] + self.costs.delete, fd[x][y]) {
+                    x -= 1;
+                } else {
+                    y -= 1;
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Like `get_matching_ids`, but the elements are matched by a minimum-cost
+/// edit script, that can also match elements on different levels.
+/// The pairs in `seed` take precedence over the edit script.
+pub(crate) fn get_matching_ids_by_edit_distance<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    costs: &EditCosts,
+    seed: &Anchors,
+    g: &mut MatchingIdGenerator,
+) -> (SVGWithMatchingState<'a>, SVGWithMatchingState<'a>) {
+    let mut edit_distance = EditDistance::new(origin, target, costs);
+    let mut pairs = Anchors::default();
+    pairs.extend(seed);
+    for (i, j) in edit_distance.mapping() {
+        pairs.insert(
+            edit_distance.origin.positions[i],
+            edit_distance.target.positions[j],
+            EDIT_DISTANCE_RULE,
+        );
+    }
+    matching_states_from_pairs(origin, target, &pairs, g)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_distance() {
+        let origin = SVG::parse_svg_string(
+            r#"<svg><g><rect width="1"/><circle r="2"/></g><text>a</text></svg>"#,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r#"<svg><g><circle r="3"/></g><text>a</text><line x2="1"/></svg>"#,
+        )
+        .unwrap();
+        let costs = EditCosts::default();
+        let mut edit_distance = EditDistance::new(&origin, &target, &costs);
+        // Remove the rect, change the radius and add the line
+        let n = edit_distance.origin.len();
+        let m = edit_distance.target.len();
+        assert!(same_cost(edit_distance.tree_dist[n][m], 2.25));
+        // The root, the group, the circle and the text are kept
+        assert_eq!(edit_distance.mapping().len(), 4);
+    }
+
+    #[test]
+    fn expensive_changes() {
+        let origin = SVG::parse_svg_string(r#"<svg><rect width="1" height="1"/></svg>"#).unwrap();
+        let target = SVG::parse_svg_string(r#"<svg><rect width="2" height="2"/></svg>"#).unwrap();
+        let costs = EditCosts {
+            attribute: 1.5,
+            ..Default::default()
+        };
+        let mut edit_distance = EditDistance::new(&origin, &target, &costs);
+        // Replacing the rect is cheaper than changing both attributes
+        assert_eq!(edit_distance.mapping().len(), 1);
+    }
+}
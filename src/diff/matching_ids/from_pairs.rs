@@ -0,0 +1,167 @@
+//! Matching states for matchers, that match the elements of the whole trees
+//! at once instead of parent by parent (see `get_matching_ids`).
+
+use crate::diff::matching_ids::{
+    anchors::Anchors, generator::MatchingIdGenerator, matching_state::MatchingState,
+    set_matching_ids::ROOT_RULE,
+};
+use crate::svg_data::{SVGWithMatchingState, TreeHash};
+use crate::SVG;
+use flange_flat_tree::{Subtree, Tree};
+
+fn id_attr(svg: &SVG, pos: usize) -> Option<String> {
+    svg.tags
+        .at_pos(pos)
+        .value()
+        .args
+        .get("id")
+        .map(|a| a.to_string())
+}
+
+/// Whether the element is matched and its children have to be matched too.
+fn has_open_children(state: &Option<MatchingState>) -> bool {
+    state
+        .as_ref()
+        .map(|s| !s.is_unmatched() && !s.full_match())
+        .unwrap_or(false)
+}
+
+/// Marks the element at `pos` and all its descendants.
+fn cover(svg: &SVG, pos: usize, covered: &mut [bool]) {
+    covered[pos] = true;
+    for &child in svg.tags.get_nav().children(pos).iter() {
+        cover(svg, child, covered);
+    }
+}
+
+/// Whether the element at `pos` or any of its descendants is marked.
+fn any_covered(svg: &SVG, pos: usize, covered: &[bool]) -> bool {
+    covered[pos]
+        || svg
+            .tags
+            .get_nav()
+            .children(pos)
+            .iter()
+            .any(|child| any_covered(svg, *child, covered))
+}
+
+/// Creates the matching states for the matched `pairs`, the reason of every
+/// match is its rule in the pairs (see `Anchors::rule_of`).
+///
+/// Matched elements can be anywhere in the trees, also below elements
+/// that are removed or added. Like in `get_matching_ids`, the elements below
+/// identical elements (full matches) don't get an id and the root elements
+/// are always matched.
+///
+/// Unmatched elements get an id if their parent is matched (so they are removed
+/// or added). In the target they also get one if they contain matched elements,
+/// so these can be moved into them.
+pub(crate) fn matching_states_from_pairs<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    pairs: &Anchors,
+    g: &mut MatchingIdGenerator,
+) -> (SVGWithMatchingState<'a>, SVGWithMatchingState<'a>) {
+    let origin_with_treehash = TreeHash::build_for_svg(origin, &Vec::new());
+    let target_with_treehash = TreeHash::build_for_svg(target, &Vec::new());
+    let root = (origin.tags.root().get_pos(), target.tags.root().get_pos());
+    let mut matches = vec![(root.0, root.1, ROOT_RULE)];
+    let mut origin_order = origin.positions();
+    origin_order.retain(|o_index| *o_index != root.0);
+    for o_index in origin_order {
+        match pairs.target_of(o_index) {
+            Some(t_index) if t_index != root.1 => {
+                matches.push((o_index, t_index, pairs.rule_of(o_index)))
+            }
+            _ => {}
+        }
+    }
+
+    // The identical elements, the descendants of those are not matched separately
+    let mut origin_covered = vec![false; origin.tags.node_count()];
+    let mut target_covered = vec![false; target.tags.node_count()];
+    let mut full_matches = Vec::new();
+    for (o_index, t_index, _) in matches.iter() {
+        if origin_with_treehash
+            .get_flange(*o_index)
+            .eq_all(target_with_treehash.get_flange(*t_index))
+            && !any_covered(origin, *o_index, &origin_covered)
+            && !any_covered(target, *t_index, &target_covered)
+        {
+            cover(origin, *o_index, &mut origin_covered);
+            cover(target, *t_index, &mut target_covered);
+            full_matches.push((*o_index, *t_index));
+        }
+    }
+
+    let mut origin_ids = vec![None; origin.tags.node_count()];
+    let mut target_ids = vec![None; target.tags.node_count()];
+    for (o_index, t_index, rule) in matches {
+        let covered = origin_covered[o_index] || target_covered[t_index];
+        if covered && !full_matches.contains(&(o_index, t_index)) {
+            continue;
+        }
+        let state = MatchingState::new(
+            g,
+            o_index,
+            t_index,
+            origin_with_treehash.get_flange(o_index),
+            target_with_treehash.get_flange(t_index),
+            rule,
+            id_attr(origin, o_index),
+        );
+        origin_ids[o_index] = Some(state.clone());
+        target_ids[t_index] = Some(state);
+    }
+
+    // The remaining children of matched elements are removed or added
+    for o_index in origin.positions() {
+        if origin_ids[o_index].is_none()
+            && !origin_covered[o_index]
+            && origin
+                .parent_position(o_index)
+                .map(|p| has_open_children(&origin_ids[p]))
+                .unwrap_or(false)
+        {
+            origin_ids[o_index] = Some(MatchingState::new_unmatched(
+                o_index,
+                true,
+                g,
+                id_attr(origin, o_index),
+            ));
+        }
+    }
+    // Added elements that contain matched elements
+    let target_order = target.positions();
+    let mut contains_matched = vec![false; target.tags.node_count()];
+    for t_index in target_order.iter().rev() {
+        contains_matched[*t_index] = target.tags.get_nav().children(*t_index).iter().any(|c| {
+            contains_matched[*c]
+                || target_ids[*c]
+                    .as_ref()
+                    .map(|s| !s.is_unmatched())
+                    .unwrap_or(false)
+        });
+    }
+    for t_index in target_order {
+        if target_ids[t_index].is_none()
+            && !target_covered[t_index]
+            && (contains_matched[t_index]
+                || target
+                    .parent_position(t_index)
+                    .map(|p| has_open_children(&target_ids[p]))
+                    .unwrap_or(false))
+        {
+            target_ids[t_index] = Some(MatchingState::new_unmatched(
+                t_index,
+                false,
+                g,
+                id_attr(target, t_index),
+            ));
+        }
+    }
+    (
+        origin.with_matching_states(origin_ids),
+        target.with_matching_states(target_ids),
+    )
+}
@@ -2,7 +2,11 @@
 //! Whenever a pair of SVG elements is matched
 //! they get a new ID generate by this generator.
 
+use crate::config::IdOptions;
+use crate::SVG;
+use flange_flat_tree::Tree;
 use getrandom::getrandom;
+use std::collections::HashSet;
 
 /// Generates IDs for matching SVG elements.
 ///
@@ -12,48 +16,207 @@ use getrandom::getrandom;
 /// The prefix is generated for every instance
 /// of the `MatchingIdGenerator`. This ensures
 /// that IDs from different generators do not
-/// overlap.
+/// overlap. It can also be fixed or seeded (see `IdOptions`).
 /// The number is just an integer number, that is
 /// increased by one with for every ID.
+///
+/// Content derived IDs (see `IdOptions::content_derived`) look like
+/// "<tag name>-<hash>" instead.
 pub struct MatchingIdGenerator {
     /// A common prefix for all generated ID.
     prefix: String,
     /// The next index to use for generating an ID.
     next_index: u64,
+    /// The elements of the SVGs, if the IDs are derived from them.
+    content: Option<ContentKeys>,
+    /// The content derived IDs so far.
+    used: HashSet<String>,
+    /// The IDs the origin elements already have (from a previous diff), by position.
+    carried: Vec<Option<String>>,
+    /// Whether the `id` attributes of the elements are used as IDs
+    /// (see `IdOptions::attribute`).
+    use_default_ids: bool,
+}
+
+/// The tag name and the path of every element of the matched SVGs.
+struct ContentKeys {
+    origin: Vec<(String, String)>,
+    target: Vec<(String, String)>,
+}
+
+impl ContentKeys {
+    fn new(origin: &SVG, target: &SVG) -> ContentKeys {
+        let keys = |svg: &SVG| -> Vec<(String, String)> {
+            (0..svg.tags.node_count())
+                .map(|pos| {
+                    let path: Vec<String> =
+                        svg.index_path(pos).iter().map(|i| i.to_string()).collect();
+                    (svg.tag(pos).name().to_string(), path.join("/"))
+                })
+                .collect()
+        };
+        ContentKeys {
+            origin: keys(origin),
+            target: keys(target),
+        }
+    }
 }
 
+const CHARS: &str = "abcdefghijklmnopqrstuvwxyz";
+
 fn random_string(length: usize) -> String {
     let mut buffer = vec![0u8; length];
     getrandom(&mut buffer).unwrap();
     let mut res = String::with_capacity(length);
-    let chars: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
+    let chars: Vec<char> = CHARS.chars().collect();
     for i in 0..length {
         res.push(chars[(buffer[i] as usize) % chars.len()]);
     }
     res
 }
 
+/// Like `random_string`, but the same for the same seed (with splitmix64).
+fn seeded_string(length: usize, seed: u64) -> String {
+    let chars: Vec<char> = CHARS.chars().collect();
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+            chars[(z % chars.len() as u64) as usize]
+        })
+        .collect()
+}
+
+/// A hash that is the same on every platform and in every version (FNV-1a).
+fn stable_hash(value: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in value.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
 impl MatchingIdGenerator {
-    /// New generator, sets the prefix to something random and the id to 0.
-    pub fn new() -> MatchingIdGenerator {
+    /// New generator, sets the prefix (as given by the options) and the id to 0.
+    pub fn new(options: &IdOptions) -> MatchingIdGenerator {
+        let prefix = match (&options.prefix, options.seed) {
+            (Some(prefix), _) => prefix.clone(),
+            (None, Some(seed)) => seeded_string(8, seed),
+            (None, None) => random_string(8),
+        };
         MatchingIdGenerator {
-            prefix: random_string(8),
+            prefix,
             next_index: 0,
+            content: None,
+            used: HashSet::new(),
+            carried: Vec::new(),
+            use_default_ids: options.uses_id_attribute(),
         }
     }
 
-    /// Generates a new ID.
+    /// Derives the next IDs from the elements of these SVGs
+    /// (see `IdOptions::content_derived`).
+    pub(crate) fn derive_from(&mut self, origin: &SVG, target: &SVG) {
+        self.content = Some(ContentKeys::new(origin, target));
+    }
+
+    /// Sets the IDs the origin elements of the next diff already have,
+    /// so the elements keep them (see `diffs`).
+    pub(crate) fn carry(&mut self, ids: Vec<Option<String>>) {
+        self.carried = ids;
+    }
+
+    /// Keeps the carried IDs of the elements at `positions` (in this order),
+    /// for diffing a copy with only these elements (see `SVG::without_ignored`).
+    pub(crate) fn keep_carried(&mut self, positions: &[usize]) {
+        self.carried = positions
+            .iter()
+            .map(|pos| self.carried.get(*pos).cloned().flatten())
+            .collect();
+    }
+
+    /// Generates a new ID for the elements at the indices in the origin and the target.
+    /// If the origin element has a carried ID, it is kept.
     /// If default_id is set to something but none, it is simple
-    /// returned.
+    /// returned (unless the IDs are written to another attribute than `id`).
     /// The Idea is, that if one of the elements has already an ID attribute,
     /// that can be used for the ID.
-    pub fn next(&mut self, default_id: Option<String>) -> String {
-        if let Some(pre_id) = default_id {
-            pre_id
-        } else {
-            let res = format!("{}-{}", self.prefix, self.next_index);
-            self.next_index += 1;
-            res
+    pub fn next(
+        &mut self,
+        origin_index: Option<usize>,
+        target_index: Option<usize>,
+        default_id: Option<String>,
+    ) -> String {
+        if let Some(carried) = origin_index.and_then(|i| self.carried.get(i).cloned().flatten()) {
+            return carried;
+        }
+        if let Some(pre_id) = default_id.filter(|_| self.use_default_ids) {
+            return pre_id;
         }
+        if let Some(content) = &self.content {
+            let origin = origin_index.map(|i| &content.origin[i]);
+            let target = target_index.map(|i| &content.target[i]);
+            let name = origin.or(target).map(|k| k.0.as_str()).unwrap_or("e");
+            let key = format!(
+                "{}|{}|{}",
+                name,
+                origin.map(|k| k.1.as_str()).unwrap_or("-"),
+                target.map(|k| k.1.as_str()).unwrap_or("-")
+            );
+            let id = format!("{}-{:08x}", name, stable_hash(&key));
+            // Hash collisions are numbered
+            let mut res = id.clone();
+            let mut n = 1;
+            while !self.used.insert(res.clone()) {
+                res = format!("{}-{}", id, n);
+                n += 1;
+            }
+            return res;
+        }
+        let res = format!("{}-{}", self.prefix, self.next_index);
+        self.next_index += 1;
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deterministic_ids() {
+        let seeded = IdOptions {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let first = MatchingIdGenerator::new(&seeded).next(None, None, None);
+        assert_eq!(
+            first,
+            MatchingIdGenerator::new(&seeded).next(None, None, None)
+        );
+        let fixed = IdOptions {
+            prefix: Some("anim".to_string()),
+            ..Default::default()
+        };
+        let mut g = MatchingIdGenerator::new(&fixed);
+        assert_eq!(g.next(None, None, None), "anim-0");
+        assert_eq!(g.next(None, None, Some("a".to_string())), "a");
+        assert_eq!(g.next(None, None, None), "anim-1");
+
+        let svg = SVG::parse_svg_string(r#"<svg><rect/><rect/></svg>"#).unwrap();
+        let mut g = MatchingIdGenerator::new(&IdOptions::default());
+        g.derive_from(&svg, &svg);
+        let a = g.next(Some(1), Some(1), None);
+        let b = g.next(Some(2), Some(2), None);
+        assert!(a.starts_with("rect-"));
+        assert_ne!(a, b);
+        let mut other = MatchingIdGenerator::new(&IdOptions::default());
+        other.derive_from(&svg, &svg);
+        assert_eq!(other.next(Some(1), Some(1), None), a);
     }
 }
@@ -0,0 +1,266 @@
+//! Anchoring of the nodes and edges of graph diagrams (see `GraphMatching`).
+
+use crate::config::GraphMatching;
+use crate::diff::matching_ids::anchors::Anchors;
+use crate::svg_data::Tag;
+use crate::SVG;
+use flange_flat_tree::{Subtree, Tree};
+use log::debug;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::OnceLock;
+
+/// The name reported for matches of graph nodes and edges.
+pub(crate) const GRAPH_RULE: &str = "graph";
+
+fn entity_regex() -> &'static Regex {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    ENTITY.get_or_init(|| Regex::new(r"&(#[0-9]+|#x[0-9a-fA-F]+|[a-z]+);").unwrap())
+}
+
+/// Replaces the XML entities (like `&#45;&gt;`) in a text by their characters.
+fn decode_entities(text: &str) -> String {
+    entity_regex()
+        .replace_all(text, |c: &regex::Captures| {
+            let entity = &c[1];
+            let decoded = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+                }
+                .and_then(char::from_u32),
+            };
+            decoded
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| c[0].to_string())
+        })
+        .to_string()
+}
+
+/// The nodes and edges of a graph in an SVG.
+#[derive(Debug, Default)]
+struct Graph {
+    /// The node groups by their name.
+    nodes: BTreeMap<String, usize>,
+    /// The edge groups with the names of the nodes they connect, in document order.
+    edges: Vec<(usize, String, String)>,
+}
+
+impl Graph {
+    fn from_svg(svg: &SVG, graph: &GraphMatching) -> Graph {
+        let mut res = Graph::default();
+        for pos in 0..svg.tags.node_count() {
+            let tag = svg.tags.at_pos(pos).value();
+            let ancestors: Vec<&Tag> = svg
+                .ancestor_positions(pos)
+                .into_iter()
+                .map(|a| svg.tags.at_pos(a).value())
+                .collect();
+            if graph.nodes.matches(tag, &ancestors) {
+                let name = match &graph.name_attribute {
+                    Some(attr) => tag.args.get(attr).map(|v| v.to_string()),
+                    None => title(svg, pos, graph),
+                };
+                if let Some(name) = name {
+                    if res.nodes.contains_key(&name) {
+                        debug!("ignoring duplicate graph node {}", name);
+                    } else {
+                        res.nodes.insert(name, pos);
+                    }
+                }
+            } else if graph.edges.matches(tag, &ancestors) {
+                if let Some((from, to)) = endpoints(svg, pos, graph) {
+                    res.edges.push((pos, from, to));
+                }
+            }
+        }
+        res
+    }
+
+    /// The names of the nodes connected to each node.
+    fn neighbours(&self) -> HashMap<&str, BTreeSet<&str>> {
+        let mut res: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for (_, from, to) in &self.edges {
+            res.entry(from.as_str()).or_default().insert(to.as_str());
+            res.entry(to.as_str()).or_default().insert(from.as_str());
+        }
+        res
+    }
+}
+
+/// The (decoded) text of the title child of the group at `pos`.
+fn title(svg: &SVG, pos: usize, graph: &GraphMatching) -> Option<String> {
+    svg.tags
+        .at_pos(pos)
+        .children()
+        .into_iter()
+        .find(|c| c.value().name == graph.title)
+        .map(|c| decode_entities(c.value().text.trim()))
+}
+
+/// The names of the nodes connected by the edge at `pos`.
+fn endpoints(svg: &SVG, pos: usize, graph: &GraphMatching) -> Option<(String, String)> {
+    if let Some((from, to)) = &graph.endpoint_attributes {
+        let tag = svg.tags.at_pos(pos).value();
+        return Some((
+            tag.args.get(from)?.to_string(),
+            tag.args.get(to)?.to_string(),
+        ));
+    }
+    let title = title(svg, pos, graph)?;
+    graph.separators.iter().find_map(|separator| {
+        title
+            .split_once(separator.as_str())
+            .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+    })
+}
+
+/// Matches the nodes of the graphs, the names in the origin to the names in the target.
+///
+/// Nodes with equal names are matched. The remaining (renamed) nodes are matched
+/// with the node sharing the largest ratio of (matched) neighbours.
+fn match_nodes(origin: &Graph, target: &Graph) -> HashMap<String, String> {
+    let mut res: HashMap<String, String> = origin
+        .nodes
+        .keys()
+        .filter(|name| target.nodes.contains_key(*name))
+        .map(|name| (name.clone(), name.clone()))
+        .collect();
+    let origin_neighbours = origin.neighbours();
+    let target_neighbours = target.neighbours();
+    let mut candidates = Vec::new();
+    for o_name in origin.nodes.keys().filter(|n| !res.contains_key(*n)) {
+        let mapped: BTreeSet<&str> = origin_neighbours
+            .get(o_name.as_str())
+            .map(|n| {
+                n.iter()
+                    .filter_map(|n| res.get(*n).map(|m| m.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        for t_name in target
+            .nodes
+            .keys()
+            .filter(|n| !origin.nodes.contains_key(*n))
+        {
+            let neighbours = match target_neighbours.get(t_name.as_str()) {
+                Some(n) => n,
+                None => continue,
+            };
+            let shared = mapped.intersection(neighbours).count();
+            if shared > 0 {
+                let all = mapped.union(neighbours).count();
+                candidates.push((shared as f64 / all as f64, o_name, t_name));
+            }
+        }
+    }
+    // The best candidates first, by name for equal scores
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+    let mut renamed_targets = BTreeSet::new();
+    for (_, o_name, t_name) in candidates {
+        if !res.contains_key(o_name) && renamed_targets.insert(t_name) {
+            debug!("graph node {} renamed to {}", o_name, t_name);
+            res.insert(o_name.clone(), t_name.clone());
+        }
+    }
+    res
+}
+
+/// Anchors the nodes and edges of the graphs in the SVGs.
+/// Elements that are already anchored (by the strategy or their keys) are left alone.
+pub(crate) fn anchor_graph(
+    origin: &SVG,
+    target: &SVG,
+    graph: &GraphMatching,
+    anchors: &mut Anchors,
+) {
+    let origin_graph = Graph::from_svg(origin, graph);
+    let target_graph = Graph::from_svg(target, graph);
+    let names = match_nodes(&origin_graph, &target_graph);
+    let mut node_pairs: Vec<(usize, usize)> = names
+        .iter()
+        .map(|(o_name, t_name)| (origin_graph.nodes[o_name], target_graph.nodes[t_name]))
+        .collect();
+    node_pairs.sort();
+    for (o_index, t_index) in node_pairs {
+        anchors.insert(o_index, t_index, GRAPH_RULE);
+    }
+    // The target edges by the nodes they connect
+    let mut target_edges: HashMap<(&str, &str), VecDeque<usize>> = HashMap::new();
+    for (pos, from, to) in &target_graph.edges {
+        target_edges
+            .entry((from.as_str(), to.as_str()))
+            .or_default()
+            .push_back(*pos);
+    }
+    for (o_index, from, to) in &origin_graph.edges {
+        let (from, to) = match (names.get(from), names.get(to)) {
+            (Some(from), Some(to)) => (from.as_str(), to.as_str()),
+            _ => continue,
+        };
+        // Undirected edges might be written the other way around
+        let t_index = [(from, to), (to, from)].iter().find_map(|key| {
+            target_edges
+                .get_mut(key)
+                .and_then(|candidates| candidates.pop_front())
+        });
+        if let Some(t_index) = t_index {
+            anchors.insert(*o_index, t_index, GRAPH_RULE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entities() {
+        assert_eq!(decode_entities("a&#45;&gt;b"), "a->b");
+        assert_eq!(decode_entities("x&#x2d;&#x2D;y &amp; z"), "x--y & z");
+        assert_eq!(decode_entities("&unknown; &#xzz;"), "&unknown; &#xzz;");
+    }
+
+    #[test]
+    fn renamed_nodes() {
+        let graph = GraphMatching::default();
+        let origin = SVG::parse_svg_string(
+            r#"<svg>
+              <g class="node"><title>a</title></g>
+              <g class="node"><title>b</title></g>
+              <g class="node"><title>c</title></g>
+              <g class="edge"><title>a&#45;&gt;b</title></g>
+              <g class="edge"><title>b&#45;&gt;c</title></g>
+            </svg>"#,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r#"<svg>
+              <g class="node"><title>a</title></g>
+              <g class="node"><title>c</title></g>
+              <g class="node"><title>d</title></g>
+              <g class="edge"><title>d&#45;&gt;c</title></g>
+              <g class="edge"><title>a&#45;&gt;d</title></g>
+            </svg>"#,
+        )
+        .unwrap();
+        let names = match_nodes(
+            &Graph::from_svg(&origin, &graph),
+            &Graph::from_svg(&target, &graph),
+        );
+        assert_eq!(names.get("b").map(|n| n.as_str()), Some("d"));
+
+        let mut anchors = Anchors::default();
+        anchor_graph(&origin, &target, &graph, &mut anchors);
+        // All 3 nodes and both edges
+        assert_eq!(anchors.pairs().len(), 5);
+        let edge = Graph::from_svg(&origin, &graph).edges[0].0;
+        let target_edge = Graph::from_svg(&target, &graph).edges[1].0;
+        assert_eq!(anchors.target_of(edge), Some(target_edge));
+    }
+}
@@ -0,0 +1,352 @@
+//! Matching in the style of GumTree (see `Algorithm::GumTree`).
+//!
+//! First identical subtrees are matched anywhere in the trees, the highest
+//! first (top-down). Then the elements containing matched elements are matched
+//! by the ratio of their matched descendants (bottom-up).
+
+use crate::config::GumTreeOptions;
+use crate::diff::matching_ids::{
+    anchors::Anchors, from_pairs::matching_states_from_pairs, generator::MatchingIdGenerator,
+    set_matching_ids::ROOT_RULE,
+};
+use crate::svg_data::{SVGWithMatchingState, SVGWithTreeHash, TreeHash};
+use crate::SVG;
+use flange_flat_tree::{Subtree, Tree};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The name reported for identical subtrees matched top-down.
+pub(crate) const IDENTICAL_RULE: &str = "identical_subtree";
+/// The name reported for elements matched by their matched descendants.
+pub(crate) const CONTAINER_RULE: &str = "container";
+
+/// An SVG with the structure needed by the matcher.
+struct Indexed<'a> {
+    svg: &'a SVG,
+    hashes: SVGWithTreeHash<'a>,
+    /// The positions in pre-order.
+    order: Vec<usize>,
+    /// The index of every element in `order`.
+    index: Vec<usize>,
+    /// The number of elements in the subtree of every element (including itself).
+    size: Vec<usize>,
+    height: Vec<usize>,
+}
+
+impl<'a> Indexed<'a> {
+    fn new(svg: &'a SVG) -> Indexed<'a> {
+        let order = svg.positions();
+        let mut index = vec![0; order.len()];
+        for (i, pos) in order.iter().enumerate() {
+            index[*pos] = i;
+        }
+        let mut size = vec![1; order.len()];
+        let mut height = vec![1; order.len()];
+        for pos in order.iter().rev() {
+            for &child in svg.tags.get_nav().children(*pos).iter() {
+                size[*pos] += size[child];
+                height[*pos] = height[*pos].max(height[child] + 1);
+            }
+        }
+        Indexed {
+            svg,
+            hashes: TreeHash::build_for_svg(svg, &Vec::new()),
+            order,
+            index,
+            size,
+            height,
+        }
+    }
+
+    fn children(&self, pos: usize) -> Vec<usize> {
+        self.svg.child_positions(pos)
+    }
+
+    fn parent(&self, pos: usize) -> Option<usize> {
+        self.svg.parent_position(pos)
+    }
+
+    fn hash(&self, pos: usize) -> u64 {
+        self.hashes.get_flange(pos).all_hash()
+    }
+
+    fn name(&self, pos: usize) -> &str {
+        &self.svg.tags.at_pos(pos).value().name
+    }
+
+    /// The element and its descendants in pre-order.
+    fn subtree(&self, pos: usize) -> &[usize] {
+        &self.order[self.index[pos]..self.index[pos] + self.size[pos]]
+    }
+
+    fn descendants(&self, pos: usize) -> &[usize] {
+        &self.subtree(pos)[1..]
+    }
+
+    fn is_descendant(&self, pos: usize, ancestor: usize) -> bool {
+        self.index[pos] > self.index[ancestor]
+            && self.index[pos] < self.index[ancestor] + self.size[ancestor]
+    }
+}
+
+/// The elements still to be matched top-down, by their height.
+struct HeightQueue(BTreeMap<usize, Vec<usize>>);
+
+impl HeightQueue {
+    fn new(tree: &Indexed) -> HeightQueue {
+        let root = tree.svg.tags.root().get_pos();
+        HeightQueue(BTreeMap::from([(tree.height[root], vec![root])]))
+    }
+
+    fn max_height(&self) -> usize {
+        self.0.keys().next_back().cloned().unwrap_or(0)
+    }
+
+    fn pop(&mut self) -> Vec<usize> {
+        self.0
+            .pop_last()
+            .map(|(_, nodes)| nodes)
+            .unwrap_or_default()
+    }
+
+    /// Adds the children of the element.
+    fn open(&mut self, tree: &Indexed, pos: usize) {
+        for child in tree.children(pos) {
+            self.0.entry(tree.height[child]).or_default().push(child);
+        }
+    }
+}
+
+/// Matches the identical subtrees (and all their descendants).
+fn match_subtrees(origin: &Indexed, target: &Indexed, a: usize, b: usize, pairs: &mut Anchors) {
+    for (o_index, t_index) in origin.subtree(a).iter().zip(target.subtree(b)) {
+        pairs.insert(*o_index, *t_index, IDENTICAL_RULE);
+    }
+}
+
+/// The ratio of the descendants of `a` and `b` that are matched with each other.
+fn dice(origin: &Indexed, target: &Indexed, a: usize, b: usize, pairs: &Anchors) -> f64 {
+    let total = origin.size[a] + target.size[b] - 2;
+    if total == 0 {
+        return 0.0;
+    }
+    let common = origin
+        .descendants(a)
+        .iter()
+        .filter(|d| {
+            pairs
+                .target_of(**d)
+                .map(|t| target.is_descendant(t, b))
+                .unwrap_or(false)
+        })
+        .count();
+    2.0 * common as f64 / total as f64
+}
+
+/// Matches the identical subtrees, the highest first.
+/// Subtrees that are identical to several others are matched last,
+/// those whose parents are most similar first.
+fn top_down(origin: &Indexed, target: &Indexed, min_height: usize, pairs: &mut Anchors) {
+    let mut origin_queue = HeightQueue::new(origin);
+    let mut target_queue = HeightQueue::new(target);
+    let mut ambiguous = Vec::new();
+    loop {
+        let height = origin_queue.max_height().min(target_queue.max_height());
+        if height < min_height.max(1) {
+            break;
+        }
+        if origin_queue.max_height() > height {
+            for pos in origin_queue.pop() {
+                origin_queue.open(origin, pos);
+            }
+            continue;
+        }
+        if target_queue.max_height() > height {
+            for pos in target_queue.pop() {
+                target_queue.open(target, pos);
+            }
+            continue;
+        }
+        let origin_nodes = origin_queue.pop();
+        let target_nodes = target_queue.pop();
+        let mut target_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for pos in target_nodes.iter() {
+            target_by_hash
+                .entry(target.hash(*pos))
+                .or_default()
+                .push(*pos);
+        }
+        let mut origin_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for pos in origin_nodes.iter() {
+            origin_by_hash
+                .entry(origin.hash(*pos))
+                .or_default()
+                .push(*pos);
+        }
+        let mut matched_targets = HashSet::new();
+        for a in origin_nodes.iter() {
+            let hash = origin.hash(*a);
+            match (origin_by_hash[&hash].as_slice(), target_by_hash.get(&hash)) {
+                ([_], Some(candidates)) if candidates.len() == 1 => {
+                    match_subtrees(origin, target, *a, candidates[0], pairs);
+                    matched_targets.insert(candidates[0]);
+                }
+                (_, Some(candidates)) => {
+                    ambiguous.extend(candidates.iter().map(|b| (*a, *b)));
+                    matched_targets.extend(candidates.iter().cloned());
+                }
+                (_, None) => origin_queue.open(origin, *a),
+            }
+        }
+        for b in target_nodes {
+            if !matched_targets.contains(&b) {
+                target_queue.open(target, b);
+            }
+        }
+    }
+    // The parents of the ambiguous subtrees are compared with the matches so far
+    let mut ambiguous: Vec<(f64, usize, usize)> = ambiguous
+        .into_iter()
+        .map(|(a, b)| {
+            let similarity = match (origin.parent(a), target.parent(b)) {
+                (Some(pa), Some(pb)) => dice(origin, target, pa, pb, pairs),
+                _ => 0.0,
+            };
+            (similarity, a, b)
+        })
+        .collect();
+    // The most similar parents first, by document order for equal similarities
+    ambiguous.sort_by(|x, y| {
+        y.0.total_cmp(&x.0).then(
+            (origin.index[x.1], target.index[x.2]).cmp(&(origin.index[y.1], target.index[y.2])),
+        )
+    });
+    for (_, a, b) in ambiguous {
+        if pairs.target_of(a).is_none() && pairs.origin_of(b).is_none() {
+            match_subtrees(origin, target, a, b, pairs);
+        }
+    }
+}
+
+/// Matches the unmatched children of matched containers with the same tag name,
+/// in document order.
+fn recover_children(origin: &Indexed, target: &Indexed, a: usize, b: usize, pairs: &mut Anchors) {
+    let mut target_children: Vec<usize> = target
+        .children(b)
+        .into_iter()
+        .filter(|c| pairs.origin_of(*c).is_none())
+        .collect();
+    for o_child in origin.children(a) {
+        if pairs.target_of(o_child).is_some() {
+            continue;
+        }
+        if let Some(found) = target_children
+            .iter()
+            .position(|t_child| origin.name(o_child) == target.name(*t_child))
+        {
+            pairs.insert(o_child, target_children.remove(found), CONTAINER_RULE);
+        }
+    }
+}
+
+/// Matches the elements that contain matched elements, the innermost first.
+/// An element is matched with the unmatched element with the same tag name
+/// in the target, that contains the most of its matched descendants.
+fn bottom_up(origin: &Indexed, target: &Indexed, min_dice: f64, pairs: &mut Anchors) {
+    let origin_root = origin.svg.tags.root().get_pos();
+    let target_root = target.svg.tags.root().get_pos();
+    pairs.insert(origin_root, target_root, ROOT_RULE);
+    for a in origin.order.iter().rev() {
+        let a = *a;
+        if a == origin_root {
+            if pairs.target_of(a) == Some(target_root) {
+                recover_children(origin, target, a, target_root, pairs);
+            }
+            continue;
+        }
+        if pairs.target_of(a).is_some() || origin.size[a] == 1 {
+            continue;
+        }
+        // The unmatched ancestors of the partners of the descendants
+        let mut candidates = Vec::new();
+        let mut seen = HashSet::new();
+        for d in origin.descendants(a) {
+            let mut ancestor = pairs.target_of(*d).and_then(|t| target.parent(t));
+            while let Some(b) = ancestor {
+                if !seen.insert(b) {
+                    break;
+                }
+                if pairs.origin_of(b).is_none() && origin.name(a) == target.name(b) {
+                    candidates.push(b);
+                }
+                ancestor = target.parent(b);
+            }
+        }
+        let best = candidates
+            .into_iter()
+            .map(|b| (dice(origin, target, a, b, pairs), b))
+            .filter(|(similarity, _)| *similarity >= min_dice && *similarity > 0.0)
+            .max_by(|x, y| {
+                x.0.total_cmp(&y.0)
+                    .then(target.index[y.1].cmp(&target.index[x.1]))
+            });
+        if let Some((_, b)) = best {
+            pairs.insert(a, b, CONTAINER_RULE);
+            recover_children(origin, target, a, b, pairs);
+        }
+    }
+}
+
+/// Like `get_matching_ids`, but with the GumTree matcher.
+/// The pairs in `seed` are matched before anything else.
+pub(crate) fn get_matching_ids_by_gumtree<'a>(
+    origin: &'a SVG,
+    target: &'a SVG,
+    options: &GumTreeOptions,
+    seed: &Anchors,
+    g: &mut MatchingIdGenerator,
+) -> (SVGWithMatchingState<'a>, SVGWithMatchingState<'a>) {
+    let origin_indexed = Indexed::new(origin);
+    let target_indexed = Indexed::new(target);
+    let mut pairs = Anchors::default();
+    pairs.extend(seed);
+    top_down(
+        &origin_indexed,
+        &target_indexed,
+        options.min_height,
+        &mut pairs,
+    );
+    bottom_up(
+        &origin_indexed,
+        &target_indexed,
+        options.min_dice,
+        &mut pairs,
+    );
+    matching_states_from_pairs(origin, target, &pairs, g)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn regrouped() {
+        let origin = SVG::parse_svg_string(
+            r#"<svg><g><rect width="1"/><text>a</text></g><circle r="1"/></svg>"#,
+        )
+        .unwrap();
+        let target = SVG::parse_svg_string(
+            r#"<svg><g class="new"><g><rect width="1"/><text>b</text></g></g><circle r="1"/></svg>"#,
+        )
+        .unwrap();
+        let (o, t) = (Indexed::new(&origin), Indexed::new(&target));
+        let mut pairs = Anchors::default();
+        top_down(&o, &t, 1, &mut pairs);
+        // The rect and the circle
+        assert_eq!(pairs.pairs().len(), 2);
+        bottom_up(&o, &t, 0.5, &mut pairs);
+        // The root, the inner group (by the rect) and the text (recovered)
+        assert_eq!(pairs.pairs().len(), 5);
+        let inner_group = o.order[1];
+        assert_eq!(pairs.target_of(inner_group), Some(t.order[2]));
+    }
+}
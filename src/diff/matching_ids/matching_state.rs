@@ -30,10 +30,13 @@ pub(crate) struct MatchingState {
     internal_changes: bool,
     // Has something changed inside this node
     no_match: bool, // We have no matching partner
+    // The rule that matched the elements (`None` for unmatched elements)
+    rule: Option<String>,
 }
 
 impl MatchingState {
-    /// Returns the ID as it should be stored in the "id" attribute of the element.
+    /// Returns the ID as it should be stored in the "id" attribute of the element
+    /// (or the attribute in `IdOptions::attribute`).
     pub fn get_id(&self) -> String {
         self.matching_id.to_string()
     }
@@ -45,6 +48,7 @@ impl MatchingState {
     /// - g - The generator to generate IDs.
     /// - hash - The `TreeHash` of the first (original) element.
     /// - o_hash - The `TreeHash` of the second (target) element.
+    /// - rule - The name of the rule that matched the elements.
     /// - default_id - A default ID, which (if not None) overwrites the ID from the generator. Use
     ///                this if the elements already have IDs and you want to keep them.
     ///
@@ -58,19 +62,21 @@ impl MatchingState {
         target_index: usize,
         hash: &TreeHash,
         o_hash: &TreeHash,
+        rule: &str,
         default_id: Option<String>,
     ) -> MatchingState {
         let no_changes = hash.eq_all(o_hash);
         let subtree_changes = !hash.eq_all_subtrees(o_hash);
         let internal_changes = !hash.eq_all_without_subtrees(o_hash);
         MatchingState {
-            matching_id: g.next(default_id),
+            matching_id: g.next(Some(origin_index), Some(target_index), default_id),
             origin_index: Some(origin_index),
             target_index: Some(target_index),
             no_changes,
             subtree_changes,
             internal_changes,
             no_match: false,
+            rule: Some(rule.to_string()),
         }
     }
 
@@ -92,14 +98,28 @@ impl MatchingState {
         g: &mut MatchingIdGenerator,
         default_id: Option<String>,
     ) -> MatchingState {
+        let (origin_index, target_index) = if is_origin {
+            (Some(index), None)
+        } else {
+            (None, Some(index))
+        };
         MatchingState {
-            matching_id: g.next(default_id),
+            matching_id: g.next(origin_index, target_index, default_id),
             no_changes: false,
-            origin_index: if is_origin { Some(index) } else { None },
-            target_index: if is_origin { None } else { Some(index) },
+            origin_index,
+            target_index,
             subtree_changes: false,
             internal_changes: false,
             no_match: true,
+            rule: None,
+        }
+    }
+
+    /// The same state with another ID (see `resolve_collisions`).
+    pub(crate) fn with_id(&self, matching_id: String) -> MatchingState {
+        MatchingState {
+            matching_id,
+            ..self.clone()
         }
     }
 
@@ -123,6 +143,11 @@ impl MatchingState {
         self.no_match
     }
 
+    /// The name of the rule that matched the elements.
+    pub fn matched_by(&self) -> Option<&str> {
+        self.rule.as_deref()
+    }
+
     // Return the indices
     pub fn get_target_index(&self) -> Option<usize> {
         self.target_index
@@ -6,10 +6,22 @@
 //!
 //! Matching Elements are found by different criteria (see `set_matching_ids`).
 
+mod anchors;
+mod assignment;
+mod collisions;
+mod edit_distance;
+mod from_pairs;
 mod generator;
+mod graph;
+mod gumtree;
 mod matching_state;
 mod set_matching_ids;
+pub(crate) mod similarity;
 
+pub(crate) use self::anchors::{can_match, Anchors};
+pub(crate) use self::collisions::resolve_collisions;
+pub(crate) use self::edit_distance::get_matching_ids_by_edit_distance;
 pub(crate) use self::generator::MatchingIdGenerator;
+pub(crate) use self::gumtree::get_matching_ids_by_gumtree;
 pub(crate) use self::matching_state::MatchingState;
 pub(crate) use self::set_matching_ids::get_matching_ids;
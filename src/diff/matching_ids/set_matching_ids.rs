@@ -1,9 +1,123 @@
-use crate::config::MatchingRules;
-use crate::diff::matching_ids::{generator::MatchingIdGenerator, matching_state::MatchingState};
-use crate::svg_data::{SVGWithMatchingState, SVGWithTreeHashSubtree, TreeHash};
+use crate::config::{MatchingRule, MatchingRules, SimilarityScoring};
+use crate::diff::matching_ids::{
+    anchors::Anchors, assignment, generator::MatchingIdGenerator, graph,
+    matching_state::MatchingState, similarity,
+};
+use crate::errors::*;
+use crate::svg_data::{
+    bounding_boxes, BoundingBox, SVGWithMatchingState, SVGWithTreeHash, SVGWithTreeHashSubtree,
+    TreeHash,
+};
 use crate::SVG;
 use flange_flat_tree::{Subtree, Tree};
 use log::debug;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+
+/// The name reported for the match of the root elements, which are always matched.
+pub(crate) const ROOT_RULE: &str = "root";
+
+/// Everything the matching needs, that does not change while matching.
+struct MatchingContext<'a> {
+    rule_set: &'a MatchingRules,
+    /// The resolved rules by their name.
+    rules: HashMap<String, MatchingRule>,
+    /// The bounding boxes of the origin elements (only computed if needed).
+    origin_boxes: Vec<Option<BoundingBox>>,
+    /// Same for the target
+    target_boxes: Vec<Option<BoundingBox>>,
+    /// Elements that are matched with each other before any rule is applied.
+    anchors: Anchors,
+}
+
+impl<'a> MatchingContext<'a> {
+    /// The rule, if it compares texts by similarity.
+    fn fuzzy_rule(&self, rule_name: &str) -> Option<&MatchingRule> {
+        self.rules.get(rule_name).filter(|r| r.fuzzy_text.is_some())
+    }
+
+    /// Whether pairs that match by the rule are ranked (see `rank`),
+    /// because the rule compares texts by similarity or positions.
+    fn is_ranked(&self, rule_name: &str) -> bool {
+        match self.rules.get(rule_name) {
+            Some(rule) => {
+                rule.proximity.is_some()
+                    || rule.fuzzy_text.is_some()
+                    || [&rule.prev_sibling_rule, &rule.next_sibling_rule]
+                        .iter()
+                        .any(|r| r.as_ref().and_then(|r| self.fuzzy_rule(r)).is_some())
+            }
+            None => false,
+        }
+    }
+
+    /// How well two elements that match by the hash of the rule fit together.
+    /// The rank is the dissimilarity of the texts and the distance of the elements
+    /// (lower is better), `None` if they are too different or too far apart.
+    /// Always `(0, 0)` for rules that are not ranked.
+    fn rank(
+        &self,
+        rule_name: &str,
+        a: &SVGWithTreeHashSubtree,
+        b: &SVGWithTreeHashSubtree,
+    ) -> Option<(f64, f64)> {
+        let rule = match self.rules.get(rule_name) {
+            Some(rule) => rule,
+            None => return Some((0.0, 0.0)),
+        };
+        let mut dissimilarity = 0.0;
+        if rule.fuzzy_text.is_some() {
+            dissimilarity += text_dissimilarity(rule, a, b)?;
+        }
+        for (sibling_rule, a_sibling, b_sibling) in [
+            (&rule.prev_sibling_rule, a.prev_sibling(), b.prev_sibling()),
+            (&rule.next_sibling_rule, a.next_sibling(), b.next_sibling()),
+        ] {
+            if let Some(fuzzy_rule) = sibling_rule.as_ref().and_then(|r| self.fuzzy_rule(r)) {
+                dissimilarity += text_dissimilarity(fuzzy_rule, &a_sibling?, &b_sibling?)?;
+            }
+        }
+        let distance = match &rule.proximity {
+            Some(proximity) => match (
+                &self.origin_boxes[a.get_pos()],
+                &self.target_boxes[b.get_pos()],
+            ) {
+                (Some(a_box), Some(b_box)) => proximity.distance(a_box, b_box)?,
+                _ => return None,
+            },
+            None => 0.0,
+        };
+        Some((dissimilarity, distance))
+    }
+}
+
+fn cmp_rank(a: &(f64, f64), b: &(f64, f64)) -> Ordering {
+    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+}
+
+/// The texts of the element and all its children, normalized by the rule.
+fn subtree_text(node: &SVGWithTreeHashSubtree, rule: &MatchingRule) -> String {
+    let mut texts = vec![rule.normalize_text(&node.value().0.text).trim().to_string()];
+    texts.extend(node.children().iter().map(|c| subtree_text(c, rule)));
+    texts.retain(|t| !t.is_empty());
+    texts.join(" ")
+}
+
+/// `1 - similarity` of the texts by the fuzzy text comparison of the rule,
+/// `None` if they are not similar enough.
+fn text_dissimilarity(
+    rule: &MatchingRule,
+    a: &SVGWithTreeHashSubtree,
+    b: &SVGWithTreeHashSubtree,
+) -> Option<f64> {
+    let fuzzy = rule.fuzzy_text.as_ref()?;
+    let similarity = similarity::text_similarity_by(
+        fuzzy.metric,
+        &subtree_text(a, rule),
+        &subtree_text(b, rule),
+    );
+    Some(1.0 - similarity).filter(|_| similarity >= fuzzy.min_similarity)
+}
 
 /// Find tags between `origin` and `target` that match and give them the same Matching ID.
 /// These matching IDs can than later be used to find changes between the SVGs and generate the diff.
@@ -16,6 +130,11 @@ use log::debug;
 /// The algorithm does not find matching between arbitrary Tags in the Tree.
 /// Only if Tags match are the children also checked for matches.
 ///
+/// Elements with the same key (see `MatchingRules::key_attributes`), the pairs
+/// in `seed` (see `MatchingStrategy`) and the nodes and edges of graphs
+/// (see `MatchingRules::graph`) are always matched first and are never
+/// matched with any other element.
+///
 /// The matches happen by the following priority (this with higher priority are preferred
 /// over possible matches with lower priority). See als the Documentation of `TreeHash`.
 ///
@@ -42,21 +161,56 @@ use log::debug;
 ///
 ///  - origin - The first SVG to find matches in.
 ///  - target - The second SVG to find matches in.
+///  - seed - Pairs of elements that are known to match.
 ///
 /// # Result
 ///
 /// An array for each of the svgs containing the matching IDs.
 /// The resulting arrays are indexed the same way as the tags in the SVGs.
+///
+/// # Errors
+///
+/// If a key is used by more than one element in one of the SVGs, or if
+/// the elements with the same key are matched with other elements in `seed`.
 
 pub(crate) fn get_matching_ids<'a>(
     origin: &'a SVG,
     target: &'a SVG,
     rule_set: &MatchingRules,
+    seed: &Anchors,
     g: &mut MatchingIdGenerator,
-) -> (SVGWithMatchingState<'a>, SVGWithMatchingState<'a>) {
+) -> Result<(SVGWithMatchingState<'a>, SVGWithMatchingState<'a>)> {
     // Generate the treehashes
-    let origin_with_treehash = TreeHash::build_for_svg(origin, &rule_set.rules);
-    let target_with_treehash = TreeHash::build_for_svg(target, &rule_set.rules);
+    let rules = rule_set.resolved_rules();
+    let origin_with_treehash = TreeHash::build_for_svg(origin, &rules);
+    let target_with_treehash = TreeHash::build_for_svg(target, &rules);
+
+    // The pairs of the strategy first, the keys must agree with them
+    let mut anchors = Anchors::default();
+    anchors.merge(seed)?;
+    anchors.merge(&Anchors::from_keys(
+        origin,
+        target,
+        &rule_set.key_attributes,
+    )?)?;
+    if let Some(graph_matching) = &rule_set.graph {
+        graph::anchor_graph(origin, target, graph_matching, &mut anchors);
+    }
+    let ctx = MatchingContext {
+        rule_set,
+        rules: rules.iter().map(|r| (r.name.clone(), r.clone())).collect(),
+        origin_boxes: if rule_set.needs_bounding_boxes() {
+            bounding_boxes(origin)
+        } else {
+            Vec::new()
+        },
+        target_boxes: if rule_set.needs_bounding_boxes() {
+            bounding_boxes(target)
+        } else {
+            Vec::new()
+        },
+        anchors,
+    };
 
     // Make space for the result
     let mut origin_ids = vec![None; origin.tags.node_count()];
@@ -64,23 +218,33 @@ pub(crate) fn get_matching_ids<'a>(
     set_matching_ids_rec(
         origin_with_treehash.root(),
         target_with_treehash.root(),
+        ROOT_RULE,
         &mut origin_ids,
         &mut target_ids,
-        rule_set,
+        &ctx,
+        g,
+    );
+    match_across_parents(
+        &origin_with_treehash,
+        &target_with_treehash,
+        &mut origin_ids,
+        &mut target_ids,
+        &ctx,
         g,
     );
-    (
+    Ok((
         origin.with_matching_states(origin_ids),
         target.with_matching_states(target_ids),
-    )
+    ))
 }
 
 fn set_matching_ids_rec(
     origin: SVGWithTreeHashSubtree,
     target: SVGWithTreeHashSubtree,
+    rule: &str,
     origin_ids: &mut Vec<Option<MatchingState>>,
     target_ids: &mut Vec<Option<MatchingState>>,
-    rule_set: &MatchingRules,
+    ctx: &MatchingContext,
     g: &mut MatchingIdGenerator,
 ) {
     // Get the origin tag (which we use as a default)
@@ -93,6 +257,7 @@ fn set_matching_ids_rec(
         target.get_pos(),
         origin.value().1,
         target.value().1,
+        rule,
         origin_id,
     );
     if id.is_unmatched() {
@@ -109,12 +274,33 @@ fn set_matching_ids_rec(
         // Match 100%
         return;
     }
+    // Anchored children are matched before anything else
+    for (o_child, t_child) in
+        find_anchored_child_pairs(&origin, &target, origin_ids, target_ids, &ctx.anchors)
+    {
+        let rule = ctx.anchors.rule_of(o_child.get_pos());
+        set_matching_ids_rec(o_child, t_child, rule, origin_ids, target_ids, ctx, g);
+    }
     // Find the child matches by all hashes
-    for rule_name in &rule_set.priorities {
-        while let Some((o_child, t_child)) = find_first_unmatched_child_pairs_that_matches(
-            &origin, &target, origin_ids, target_ids, rule_name,
-        ) {
-            set_matching_ids_rec(o_child, t_child, origin_ids, target_ids, rule_set, g);
+    for rule_name in &ctx.rule_set.priorities {
+        if let Some(scoring) = &ctx.rule_set.scoring {
+            for (o_child, t_child) in find_best_unmatched_child_pairs_that_match(
+                &origin, &target, origin_ids, target_ids, rule_name, scoring, ctx,
+            ) {
+                set_matching_ids_rec(o_child, t_child, rule_name, origin_ids, target_ids, ctx, g);
+            }
+        } else if ctx.is_ranked(rule_name) {
+            for (o_child, t_child) in find_best_ranked_unmatched_child_pairs_that_match(
+                &origin, &target, origin_ids, target_ids, rule_name, ctx,
+            ) {
+                set_matching_ids_rec(o_child, t_child, rule_name, origin_ids, target_ids, ctx, g);
+            }
+        } else {
+            for (o_child, t_child) in find_first_unmatched_child_pairs_that_match(
+                &origin, &target, origin_ids, target_ids, rule_name, ctx,
+            ) {
+                set_matching_ids_rec(o_child, t_child, rule_name, origin_ids, target_ids, ctx, g);
+            }
         }
     }
     // The rest remains unmatched
@@ -142,25 +328,323 @@ fn set_matching_ids_rec(
     }
 }
 
-fn find_first_unmatched_child_pairs_that_matches<'a>(
+/// Returns true if the element is unmatched and the root of an unmatched subtree.
+fn is_unmatched_root(state: &Option<MatchingState>) -> bool {
+    state.as_ref().map(|s| s.is_unmatched()).unwrap_or(false)
+}
+
+/// Returns true if the element is in an unmatched subtree (or is its root),
+/// so it is removed or added if it is not matched across parents.
+fn is_in_unmatched_subtree(
+    svg: &SVGWithTreeHash,
+    ids: &[Option<MatchingState>],
+    pos: usize,
+) -> bool {
+    let mut current = Some(svg.at_pos(pos));
+    while let Some(node) = current {
+        if let Some(state) = &ids[node.get_pos()] {
+            return state.is_unmatched();
+        }
+        current = node.parent();
+    }
+    false
+}
+
+/// Match the subtrees that could not be matched under their parent
+/// with unmatched subtrees anywhere in the other SVG
+/// (first anchored elements, than by the rules in `cross_parent_priorities`).
+///
+/// Anchored elements are matched wherever they are in the unmatched subtrees.
+/// Their ancestors in the target are added without them, so they can be moved
+/// into them (the ancestors in the origin are removed after they have been moved out).
+/// By the rules only roots of unmatched subtrees are matched, so the new parent
+/// of a matched element always exists in both SVGs.
+/// Rules with a proximity restriction are skipped, as the positions
+/// of elements with different parents can not be compared.
+/// The matched elements (and their children) are matched as usual.
+fn match_across_parents<'a>(
+    origin: &'a SVGWithTreeHash<'a>,
+    target: &'a SVGWithTreeHash<'a>,
+    origin_ids: &mut Vec<Option<MatchingState>>,
+    target_ids: &mut Vec<Option<MatchingState>>,
+    ctx: &MatchingContext,
+    g: &mut MatchingIdGenerator,
+) {
+    for (o_index, t_index) in ctx.anchors.pairs() {
+        if !is_in_unmatched_subtree(origin, origin_ids, o_index)
+            || !is_in_unmatched_subtree(target, target_ids, t_index)
+        {
+            continue;
+        }
+        debug!(
+            "Matching anchored {} and {} across parents",
+            o_index, t_index
+        );
+        origin_ids[o_index] = None;
+        target_ids[t_index] = None;
+        set_matching_ids_rec(
+            origin.at_pos(o_index),
+            target.at_pos(t_index),
+            ctx.anchors.rule_of(o_index),
+            origin_ids,
+            target_ids,
+            ctx,
+            g,
+        );
+        let mut parent = target.at_pos(t_index).parent();
+        while let Some(added) = parent.filter(|p| target_ids[p.get_pos()].is_none()) {
+            let added_id = added.value().0.args.get("id").map(|a| a.to_string());
+            target_ids[added.get_pos()] = Some(MatchingState::new_unmatched(
+                added.get_pos(),
+                false,
+                g,
+                added_id,
+            ));
+            parent = added.parent();
+        }
+    }
+    for rule_name in &ctx.rule_set.cross_parent_priorities {
+        if ctx
+            .rules
+            .get(rule_name)
+            .map(|r| r.proximity.is_some())
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let origin_unmatched: Vec<usize> = (0..origin_ids.len())
+            .filter(|i| is_unmatched_root(&origin_ids[*i]) && ctx.anchors.target_of(*i).is_none())
+            .collect();
+        // The unmatched targets by their hash
+        let mut target_unmatched: HashMap<u64, Vec<usize>> = HashMap::new();
+        for t_index in 0..target_ids.len() {
+            if is_unmatched_root(&target_ids[t_index]) && ctx.anchors.origin_of(t_index).is_none() {
+                if let Some(hash) = target.get_flange(t_index).rule_hash(rule_name) {
+                    target_unmatched.entry(hash).or_default().push(t_index);
+                }
+            }
+        }
+        for o_index in origin_unmatched {
+            let candidates = match origin
+                .get_flange(o_index)
+                .rule_hash(rule_name)
+                .and_then(|hash| target_unmatched.get_mut(&hash))
+            {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+            // The best ranked candidate, the first one for rules that are not ranked
+            let found = candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(found, t_index)| {
+                    ctx.rank(rule_name, &origin.at_pos(o_index), &target.at_pos(*t_index))
+                        .map(|rank| (rank, found))
+                })
+                .min_by(|x, y| cmp_rank(&x.0, &y.0))
+                .map(|(_, found)| found);
+            if let Some(found) = found {
+                let t_index = candidates.remove(found);
+                debug!("Matching {} and {} across parents", o_index, t_index);
+                origin_ids[o_index] = None;
+                target_ids[t_index] = None;
+                set_matching_ids_rec(
+                    origin.at_pos(o_index),
+                    target.at_pos(t_index),
+                    rule_name,
+                    origin_ids,
+                    target_ids,
+                    ctx,
+                    g,
+                );
+            }
+        }
+    }
+}
+
+/// Finds the pairs of unmatched children that are anchored to each other.
+fn find_anchored_child_pairs<'a>(
     a: &'a SVGWithTreeHashSubtree<'a>,
     b: &'a SVGWithTreeHashSubtree<'a>,
-    origin_ids: &mut [Option<MatchingState>],
-    target_ids: &mut [Option<MatchingState>],
+    origin_ids: &[Option<MatchingState>],
+    target_ids: &[Option<MatchingState>],
+    anchors: &Anchors,
+) -> Vec<(SVGWithTreeHashSubtree<'a>, SVGWithTreeHashSubtree<'a>)> {
+    let mut b_children: HashMap<usize, SVGWithTreeHashSubtree<'a>> = b
+        .children()
+        .into_iter()
+        .filter(|c| target_ids[c.get_pos()].is_none())
+        .map(|c| (c.get_pos(), c))
+        .collect();
+    a.children()
+        .into_iter()
+        .filter(|c| origin_ids[c.get_pos()].is_none())
+        .filter_map(|a_child| {
+            let t_index = anchors.target_of(a_child.get_pos())?;
+            b_children
+                .remove(&t_index)
+                .map(|b_child| (a_child, b_child))
+        })
+        .collect()
+}
+
+/// The children that can still be matched by a rule, that is all children that are
+/// not matched yet and not anchored (anchored children are only matched with their anchor).
+fn free_children<'a, F>(
+    node: &'a SVGWithTreeHashSubtree<'a>,
+    ids: &[Option<MatchingState>],
+    anchored: F,
+) -> Vec<Option<SVGWithTreeHashSubtree<'a>>>
+where
+    F: Fn(usize) -> bool,
+{
+    node.children()
+        .into_iter()
+        .filter(|c| ids[c.get_pos()].is_none() && !anchored(c.get_pos()))
+        .map(Some)
+        .collect()
+}
+
+/// The indices of the children by their hash for the rule (in document order).
+/// Children the rule does not apply to are left out.
+fn children_by_hash(
+    children: &[Option<SVGWithTreeHashSubtree>],
     rule_name: &str,
-) -> Option<(SVGWithTreeHashSubtree<'a>, SVGWithTreeHashSubtree<'a>)> {
-    for a_child in a.children() {
-        if origin_ids[a_child.get_pos()].is_some() {
-            continue;
+) -> HashMap<u64, VecDeque<usize>> {
+    let mut res: HashMap<u64, VecDeque<usize>> = HashMap::new();
+    for (index, child) in children.iter().enumerate() {
+        if let Some(hash) = child
+            .as_ref()
+            .and_then(|c| c.value().1.rule_hash(rule_name))
+        {
+            res.entry(hash).or_default().push_back(index);
+        }
+    }
+    res
+}
+
+/// Finds all pairs of unmatched children that match by the rule.
+/// Every child of `a` (in document order) is matched with the first
+/// child of `b` that matches and is not matched yet.
+///
+/// The children of `b` are bucketed by their hash, so this is linear
+/// in the number of children.
+fn find_first_unmatched_child_pairs_that_match<'a>(
+    a: &'a SVGWithTreeHashSubtree<'a>,
+    b: &'a SVGWithTreeHashSubtree<'a>,
+    origin_ids: &[Option<MatchingState>],
+    target_ids: &[Option<MatchingState>],
+    rule_name: &str,
+    ctx: &MatchingContext,
+) -> Vec<(SVGWithTreeHashSubtree<'a>, SVGWithTreeHashSubtree<'a>)> {
+    let a_children = free_children(a, origin_ids, |i| ctx.anchors.target_of(i).is_some());
+    let mut b_children = free_children(b, target_ids, |i| ctx.anchors.origin_of(i).is_some());
+    let mut b_by_hash = children_by_hash(&b_children, rule_name);
+    let mut res = Vec::new();
+    for a_child in a_children.into_iter().flatten() {
+        let found = a_child
+            .value()
+            .1
+            .rule_hash(rule_name)
+            .and_then(|hash| b_by_hash.get_mut(&hash))
+            .and_then(|candidates| candidates.pop_front());
+        if let Some(b_index) = found {
+            res.push((a_child, b_children[b_index].take().unwrap()));
+        }
+    }
+    res
+}
+
+/// Like `find_first_unmatched_child_pairs_that_match`, but of all the pairs that match by the rule, those with the best total
+/// similarity score are chosen (see `SimilarityScoring`).
+fn find_best_unmatched_child_pairs_that_match<'a>(
+    a: &'a SVGWithTreeHashSubtree<'a>,
+    b: &'a SVGWithTreeHashSubtree<'a>,
+    origin_ids: &[Option<MatchingState>],
+    target_ids: &[Option<MatchingState>],
+    rule_name: &str,
+    scoring: &SimilarityScoring,
+    ctx: &MatchingContext,
+) -> Vec<(SVGWithTreeHashSubtree<'a>, SVGWithTreeHashSubtree<'a>)> {
+    let mut a_children = free_children(a, origin_ids, |i| ctx.anchors.target_of(i).is_some());
+    let mut b_children = free_children(b, target_ids, |i| ctx.anchors.origin_of(i).is_some());
+    let scores: Vec<Vec<Option<f64>>> = a_children
+        .iter()
+        .flatten()
+        .map(|a_child| {
+            b_children
+                .iter()
+                .flatten()
+                .map(|b_child| {
+                    if !a_child.value().1.eq_rule(rule_name, b_child.value().1) {
+                        return None;
+                    }
+                    ctx.rank(rule_name, a_child, b_child)?;
+                    let score = similarity::score(
+                        scoring,
+                        a_child.value().0,
+                        b_child.value().0,
+                        ctx.origin_boxes[a_child.get_pos()].as_ref(),
+                        ctx.target_boxes[b_child.get_pos()].as_ref(),
+                    );
+                    Some(score).filter(|s| *s >= scoring.min_score)
+                })
+                .collect()
+        })
+        .collect();
+    assignment::optimal_assignment(&scores)
+        .into_iter()
+        .map(|(a_index, b_index)| {
+            (
+                a_children[a_index].take().unwrap(),
+                b_children[b_index].take().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Finds all pairs of unmatched children that match by a ranked rule
+/// (see `MatchingContext::rank`). The best ranked pairs are matched first.
+fn find_best_ranked_unmatched_child_pairs_that_match<'a>(
+    a: &'a SVGWithTreeHashSubtree<'a>,
+    b: &'a SVGWithTreeHashSubtree<'a>,
+    origin_ids: &[Option<MatchingState>],
+    target_ids: &[Option<MatchingState>],
+    rule_name: &str,
+    ctx: &MatchingContext,
+) -> Vec<(SVGWithTreeHashSubtree<'a>, SVGWithTreeHashSubtree<'a>)> {
+    let mut a_children = free_children(a, origin_ids, |i| ctx.anchors.target_of(i).is_some());
+    let mut b_children = free_children(b, target_ids, |i| ctx.anchors.origin_of(i).is_some());
+    let b_by_hash = children_by_hash(&b_children, rule_name);
+    let mut candidates = Vec::new();
+    for (a_index, a_child) in a_children.iter().enumerate() {
+        let a_child = a_child.as_ref().unwrap();
+        let b_indices = match a_child
+            .value()
+            .1
+            .rule_hash(rule_name)
+            .and_then(|hash| b_by_hash.get(&hash))
+        {
+            Some(b_indices) => b_indices,
+            None => continue,
         };
-        for b_child in b.children() {
-            if target_ids[b_child.get_pos()].is_some() {
-                continue;
-            };
-            if a_child.value().1.eq_rule(rule_name, b_child.value().1) {
-                return Some((a_child, b_child));
+        for b_index in b_indices.iter() {
+            let b_child = b_children[*b_index].as_ref().unwrap();
+            if let Some(rank) = ctx.rank(rule_name, a_child, b_child) {
+                candidates.push((rank, a_index, *b_index));
             }
         }
     }
-    None
+    // Stable, so of equally ranked pairs the first in document order wins
+    candidates.sort_by(|x, y| cmp_rank(&x.0, &y.0));
+    let mut res = Vec::new();
+    for (_, a_index, b_index) in candidates {
+        if a_children[a_index].is_some() && b_children[b_index].is_some() {
+            res.push((
+                a_children[a_index].take().unwrap(),
+                b_children[b_index].take().unwrap(),
+            ));
+        }
+    }
+    res
 }
@@ -0,0 +1,97 @@
+//! Similarity measures between SVG elements.
+//!
+//! All measures are between 0 (nothing in common) and 1 (identical).
+
+use crate::config::{SimilarityScoring, TextMetric};
+use crate::svg_data::{BoundingBox, Tag};
+use std::collections::HashSet;
+
+/// The edit distance (Levenshtein distance) between two strings, counted in chars.
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev[b.len()]
+}
+
+/// The edit distance normalized by the length of the longer string.
+pub(crate) fn text_similarity(a: &str, b: &str) -> f64 {
+    let len = a.chars().count().max(b.chars().count());
+    if len == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / len as f64
+}
+
+/// The number of shared words divided by the number of all words (Jaccard index).
+pub(crate) fn token_overlap(a: &str, b: &str) -> f64 {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+    let all = a.union(&b).count();
+    if all == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / all as f64
+}
+
+/// The similarity of two texts by the given metric.
+pub(crate) fn text_similarity_by(metric: TextMetric, a: &str, b: &str) -> f64 {
+    match metric {
+        TextMetric::Levenshtein => text_similarity(a, b),
+        TextMetric::TokenOverlap => token_overlap(a, b),
+    }
+}
+
+/// The ratio of attributes that exist with the same value in both tags.
+pub(crate) fn attribute_similarity(a: &Tag, b: &Tag) -> f64 {
+    let equal = a
+        .args
+        .iter()
+        .filter(|(name, value)| b.args.get(*name) == Some(*value))
+        .count();
+    let all = a.args.len() + b.args.len() - equal;
+    if all == 0 {
+        return 1.0;
+    }
+    equal as f64 / all as f64
+}
+
+/// How close the elements are, the distance of the centers is scaled by `scale`.
+pub(crate) fn distance_similarity(
+    a: Option<&BoundingBox>,
+    b: Option<&BoundingBox>,
+    scale: f64,
+) -> f64 {
+    match (a, b) {
+        (Some(a), Some(b)) => 1.0 / (1.0 + a.center_distance(b) / scale),
+        (None, None) => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// The weighted similarity score of two elements.
+pub(crate) fn score(
+    scoring: &SimilarityScoring,
+    a: &Tag,
+    b: &Tag,
+    a_box: Option<&BoundingBox>,
+    b_box: Option<&BoundingBox>,
+) -> f64 {
+    let weights = scoring.attr_weight + scoring.text_weight + scoring.distance_weight;
+    if weights <= 0.0 {
+        return 1.0;
+    }
+    (scoring.attr_weight * attribute_similarity(a, b)
+        + scoring.text_weight * text_similarity(&a.text, &b.text)
+        + scoring.distance_weight * distance_similarity(a_box, b_box, scoring.distance_scale))
+        / weights
+}
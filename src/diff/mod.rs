@@ -1,10 +1,22 @@
 mod diff_funcs;
 mod hashmap_diff;
+mod lifetime;
 mod matching_ids;
+mod reorder;
+mod report;
 mod step;
+mod strategy;
 
 pub use self::diff_funcs::diff;
 pub use self::diff_funcs::diff_from_strings;
+pub use self::diff_funcs::diff_from_strings_with_lifetimes;
+pub use self::diff_funcs::diff_from_strings_with_reports;
+pub use self::diff_funcs::diff_with_report;
+pub use self::diff_funcs::diff_with_strategy;
 pub use self::diff_funcs::diffs;
+pub use self::diff_funcs::diffs_with_lifetimes;
+pub use self::lifetime::ElementLifetime;
+pub use self::report::{MatchedPair, MatchingReport, NearMiss, UnmatchedElement};
 pub use self::step::DiffStep;
+pub use self::strategy::MatchingStrategy;
 pub(crate) use matching_ids::MatchingState;
@@ -0,0 +1,52 @@
+/// Finds the children that have to be moved to reorder them.
+///
+/// `origin_positions` are the positions of the children in the origin,
+/// listed in their order in the target. The longest increasing subsequence
+/// of these positions is already in the right relative order and stays in place,
+/// the indices (into `origin_positions`) of all other children are returned.
+pub(crate) fn elements_to_move(origin_positions: &[usize]) -> Vec<usize> {
+    // tails[l] is the index of the smallest last element of an increasing subsequence of length l + 1
+    let mut tails: Vec<usize> = Vec::new();
+    // The previous element in the subsequence ending at an index
+    let mut prev: Vec<Option<usize>> = vec![None; origin_positions.len()];
+    for (index, position) in origin_positions.iter().enumerate() {
+        let len = tails.partition_point(|t| origin_positions[*t] < *position);
+        prev[index] = if len > 0 { Some(tails[len - 1]) } else { None };
+        if len == tails.len() {
+            tails.push(index);
+        } else {
+            tails[len] = index;
+        }
+    }
+    // Walk the longest subsequence backwards
+    let mut in_place = vec![false; origin_positions.len()];
+    let mut current = tails.last().cloned();
+    while let Some(index) = current {
+        in_place[index] = true;
+        current = prev[index];
+    }
+    (0..origin_positions.len())
+        .filter(|index| !in_place[*index])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn in_order() {
+        assert!(elements_to_move(&[1, 4, 7]).is_empty());
+    }
+
+    #[test]
+    fn moved_to_front() {
+        // The last element has been moved to the front, everything else stays
+        assert_eq!(elements_to_move(&[9, 1, 2, 3, 4]), vec![0]);
+    }
+
+    #[test]
+    fn swapped() {
+        assert_eq!(elements_to_move(&[1, 3, 2, 4]).len(), 1);
+    }
+}
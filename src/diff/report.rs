@@ -0,0 +1,201 @@
+//! An explanation of how the elements of two SVGs have been matched.
+//!
+//! The report is meant for debugging matching rules: it tells which rule
+//! matched every pair and, for the elements that stayed unmatched,
+//! which elements of the other SVG came closest.
+
+use flange_flat_tree::{Subtree, Tree};
+use serde::{Deserialize, Serialize};
+
+use super::matching_ids::similarity;
+use crate::config::SimilarityScoring;
+use crate::svg_data::{bounding_boxes, BoundingBox, SVGWithMatchingState};
+use crate::SVG;
+
+/// The number of near misses reported for each unmatched element.
+const NEAR_MISSES: usize = 3;
+
+/// Two elements that have been matched.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchedPair {
+    /// The id both elements got.
+    pub id: String,
+    /// The rule (from the priorities) that matched the elements.
+    /// `root` for the root elements, `key` for elements matched by their key attribute
+    /// and `graph` for the nodes and edges of graphs. The other algorithms
+    /// (see `Algorithm`) report `edit_distance`, or `identical_subtree` and `container`.
+    /// Matches of a `MatchingStrategy` report its name.
+    pub rule: String,
+    pub tag: String,
+    /// The position in the origin SVG, as child indices starting at the root.
+    pub origin_path: Vec<usize>,
+    /// The position in the target SVG, as child indices starting at the root.
+    pub target_path: Vec<usize>,
+}
+
+/// An element of the other SVG that was similar to an unmatched element.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub id: String,
+    pub path: Vec<usize>,
+    /// The similarity between 0 and 1 (see `SimilarityScoring`).
+    pub score: f64,
+}
+
+/// An element without a partner in the other SVG (it is added or removed).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnmatchedElement {
+    pub id: String,
+    pub tag: String,
+    /// True if the element is in the origin SVG (and is removed).
+    pub in_origin: bool,
+    pub path: Vec<usize>,
+    /// The most similar unmatched elements with the same tag name
+    /// in the other SVG, the best first.
+    pub near_misses: Vec<NearMiss>,
+}
+
+/// How the elements of two SVGs have been matched.
+///
+/// Only elements that got an id are listed, the children
+/// of fully matched or unmatched elements are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MatchingReport {
+    pub pairs: Vec<MatchedPair>,
+    pub unmatched: Vec<UnmatchedElement>,
+}
+
+/// The positions of the unmatched elements (the roots of unmatched subtrees).
+fn unmatched(states: &SVGWithMatchingState) -> Vec<usize> {
+    let mut res = Vec::new();
+    states.for_each(|s| {
+        if s.value()
+            .1
+            .as_ref()
+            .map(|s| s.is_unmatched())
+            .unwrap_or(false)
+        {
+            res.push(s.get_pos());
+        }
+    });
+    res
+}
+
+/// The unmatched elements of one side, with the near misses from the other side.
+fn unmatched_elements(
+    svg: &SVG,
+    states: &SVGWithMatchingState,
+    boxes: &[Option<BoundingBox>],
+    other: &SVG,
+    other_states: &SVGWithMatchingState,
+    other_boxes: &[Option<BoundingBox>],
+    in_origin: bool,
+    scoring: &SimilarityScoring,
+) -> Vec<UnmatchedElement> {
+    let candidates = unmatched(other_states);
+    unmatched(states)
+        .into_iter()
+        .map(|pos| {
+            let tag = svg.tags.at_pos(pos).value();
+            let mut near_misses: Vec<NearMiss> = candidates
+                .iter()
+                .filter(|c| other.tags.at_pos(**c).value().name == tag.name)
+                .map(|c| NearMiss {
+                    id: other_states.get_flange(*c).as_ref().unwrap().get_id(),
+                    path: other.index_path(*c),
+                    score: similarity::score(
+                        scoring,
+                        tag,
+                        other.tags.at_pos(*c).value(),
+                        boxes[pos].as_ref(),
+                        other_boxes[*c].as_ref(),
+                    ),
+                })
+                .collect();
+            near_misses.sort_by(|a, b| b.score.total_cmp(&a.score));
+            near_misses.truncate(NEAR_MISSES);
+            UnmatchedElement {
+                id: states.get_flange(pos).as_ref().unwrap().get_id(),
+                tag: tag.name.clone(),
+                in_origin,
+                path: svg.index_path(pos),
+                near_misses,
+            }
+        })
+        .collect()
+}
+
+impl MatchingReport {
+    /// The report with the paths in the origin and the target replaced
+    /// by `origin_path` and `target_path`.
+    pub(crate) fn map_paths(
+        mut self,
+        origin_path: &dyn Fn(&[usize]) -> Vec<usize>,
+        target_path: &dyn Fn(&[usize]) -> Vec<usize>,
+    ) -> MatchingReport {
+        for pair in self.pairs.iter_mut() {
+            pair.origin_path = origin_path(&pair.origin_path);
+            pair.target_path = target_path(&pair.target_path);
+        }
+        for element in self.unmatched.iter_mut() {
+            let (own_path, other_path) = if element.in_origin {
+                (origin_path, target_path)
+            } else {
+                (target_path, origin_path)
+            };
+            element.path = own_path(&element.path);
+            for near_miss in element.near_misses.iter_mut() {
+                near_miss.path = other_path(&near_miss.path);
+            }
+        }
+        self
+    }
+
+    pub(crate) fn build(
+        origin: &SVG,
+        target: &SVG,
+        origin_states: &SVGWithMatchingState,
+        target_states: &SVGWithMatchingState,
+        scoring: &SimilarityScoring,
+    ) -> MatchingReport {
+        let mut pairs = Vec::new();
+        origin_states.for_each(|s| {
+            if let Some(state) = s.value().1 {
+                if let (Some(rule), Some(target_index)) =
+                    (state.matched_by(), state.get_target_index())
+                {
+                    pairs.push(MatchedPair {
+                        id: state.get_id(),
+                        rule: rule.to_string(),
+                        tag: s.value().0.name.clone(),
+                        origin_path: origin.index_path(s.get_pos()),
+                        target_path: target.index_path(target_index),
+                    });
+                }
+            }
+        });
+        let origin_boxes = bounding_boxes(origin);
+        let target_boxes = bounding_boxes(target);
+        let mut unmatched = unmatched_elements(
+            origin,
+            origin_states,
+            &origin_boxes,
+            target,
+            target_states,
+            &target_boxes,
+            true,
+            scoring,
+        );
+        unmatched.extend(unmatched_elements(
+            target,
+            target_states,
+            &target_boxes,
+            origin,
+            origin_states,
+            &origin_boxes,
+            false,
+            scoring,
+        ));
+        MatchingReport { pairs, unmatched }
+    }
+}
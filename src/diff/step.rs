@@ -1,5 +1,5 @@
 use crate::diff::hashmap_diff::HashMapDiff;
-use crate::svg_data::{print_svg_element, Tag};
+use crate::svg_data::{canonical_paths, print_new_svg_element, print_svg_element, Addressing, Tag};
 use flange_flat_tree::Subtree;
 use serde::{Deserialize, Serialize};
 
@@ -55,6 +55,19 @@ pub struct ChangeTextDiff {
     new_text: String,
 }
 
+/// Replaces an element by an element of another shape (like a rect by a path).
+///
+/// `start_path` and `end_path` are the outlines of both elements, as path data
+/// with only absolute cubic curves (`M`, `C` and `Z`) and the same number of them,
+/// so the change can be animated by morphing them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChangeShapeDiff {
+    id: String,
+    start_path: String,
+    end_path: String,
+    svg: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "action")]
 pub enum DiffStep {
@@ -71,27 +84,58 @@ pub enum DiffStep {
     ChangeText(ChangeTextDiff),
     #[serde(rename = "move")]
     Move(MoveDiff),
+    // Replace a tag by another shape
+    #[serde(rename = "change_shape")]
+    ChangeShape(ChangeShapeDiff),
 }
 
 impl DiffStep {
-    pub fn remove<'a, ST>(svg: &'a ST) -> DiffStep
+    /// The removal of the origin element `svg`.
+    ///
+    /// Removes come after the adds and moves, so the siblings are the nearest
+    /// origin siblings that are still under the parent. `is_present` gets the
+    /// position of a sibling and whether it is before the element.
+    pub fn remove<'a, ST, F>(svg: &'a ST, is_present: F) -> DiffStep
     where
         ST: Subtree<Node = (&'a Tag, &'a Option<String>)>,
+        F: Fn(usize, bool) -> bool,
     {
+        let mut prev = svg.prev_sibling();
+        while let Some(sibling) = prev {
+            if is_present(sibling.get_pos(), true) {
+                prev = Some(sibling);
+                break;
+            }
+            prev = sibling.prev_sibling();
+        }
+        let mut next = svg.next_sibling();
+        while let Some(sibling) = next {
+            if is_present(sibling.get_pos(), false) {
+                next = Some(sibling);
+                break;
+            }
+            next = sibling.next_sibling();
+        }
         DiffStep::Remove(RemoveDiff {
             id: svg.value().1.clone().unwrap(),
             parent_id: svg.parent().and_then(|s| s.value().1.clone()).unwrap(),
-            prev_child_id: svg.prev_sibling().and_then(|s| s.value().1.clone()),
-            next_child_id: svg.next_sibling().and_then(|s| s.value().1.clone()),
+            prev_child_id: prev.and_then(|s| s.value().1.clone()),
+            next_child_id: next.and_then(|s| s.value().1.clone()),
         })
     }
 
-    pub fn add<'a, SVG>(svg: &'a SVG) -> DiffStep
+    /// The addition of the target element `svg`.
+    ///
+    /// `printed` is the same element in the complete target, with the content
+    /// that is ignored when diffing (see `Ignore`). The printed element leaves out
+    /// the children that have a matching id, these are matched elements that are
+    /// moved into it afterwards (or added by their own step, see `print_new_svg_element`).
+    pub fn add<'a, SVG>(svg: &'a SVG, printed: &'a SVG, addressing: &Addressing) -> DiffStep
     where
         SVG: Subtree<Node = (&'a Tag, &'a Option<String>)>,
     {
         DiffStep::Add(AddDiff {
-            svg: print_svg_element(svg),
+            svg: print_new_svg_element(printed, addressing),
             id: svg.value().1.clone().unwrap(),
             parent_id: svg.parent().and_then(|s| s.value().1.clone()).unwrap(),
             prev_child_id: svg.prev_sibling().and_then(|s| s.value().1.clone()),
@@ -99,44 +143,80 @@ impl DiffStep {
         })
     }
 
-    pub fn change(id: String, change: HashMapDiff<String>) -> DiffStep {
-        let adds = change
+    /// The changed properties, each list sorted by the property names.
+    /// The values refer to other elements as addressed in the origin or the target,
+    /// so properties that only changed the id they refer to are left out
+    /// (`None` if nothing else changed).
+    pub fn change(
+        id: String,
+        change: HashMapDiff<String>,
+        origin: &Addressing,
+        target: &Addressing,
+    ) -> Option<DiffStep> {
+        let mut adds: Vec<Property> = change
             .adds
             .iter()
             .map(|(prop, val)| Property {
                 prop: prop.clone(),
-                value: val.to_string(),
+                value: target.value(prop, val),
             })
             .collect();
-        let removes = change
+        adds.sort_by(|a, b| a.prop.cmp(&b.prop));
+        let mut removes: Vec<Property> = change
             .deletes
             .iter()
             .map(|(prop, val)| Property {
                 prop: prop.clone(),
-                value: val.to_string(),
+                value: origin.value(prop, val),
             })
             .collect();
-        let changes = change
+        removes.sort_by(|a, b| a.prop.cmp(&b.prop));
+        let mut changes: Vec<ChangedProperty> = change
             .changes
             .iter()
             .map(|(prop, (from, to))| ChangedProperty {
                 prop: prop.clone(),
-                start: from.to_string(),
-                end: to.to_string(),
+                start: origin.value(prop, from),
+                end: target.value(prop, to),
             })
+            .filter(|c| c.start != c.end)
             .collect();
-        DiffStep::ChangeProperties(ChangePropertiesDiff {
+        changes.sort_by(|a, b| a.prop.cmp(&b.prop));
+        if adds.is_empty() && removes.is_empty() && changes.is_empty() {
+            return None;
+        }
+        Some(DiffStep::ChangeProperties(ChangePropertiesDiff {
             id,
             adds,
             removes,
             changes,
-        })
+        }))
     }
 
     pub fn text_change(id: String, new_text: String) -> DiffStep {
         DiffStep::ChangeText(ChangeTextDiff { id, new_text })
     }
 
+    /// The change of the `origin` element into the element `svg` with another tag name.
+    /// `printed` is the same element in the complete target (see `add`).
+    pub fn change_shape<'a, ST>(
+        svg: &'a ST,
+        printed: &'a ST,
+        origin: &Tag,
+        addressing: &Addressing,
+    ) -> DiffStep
+    where
+        ST: Subtree<Node = (&'a Tag, &'a Option<String>)>,
+    {
+        let (start_path, end_path) = canonical_paths(origin, svg.value().0);
+        DiffStep::ChangeShape(ChangeShapeDiff {
+            id: svg.value().1.clone().unwrap(),
+            start_path,
+            end_path,
+            svg: print_svg_element(printed, addressing),
+        })
+    }
+
     pub fn move_element<'a, ST>(svg: &'a ST) -> DiffStep
     where
         ST: Subtree<Node = (&'a Tag, &'a Option<String>)>,
@@ -168,4 +248,8 @@ impl DiffStep {
     pub fn is_move(&self) -> bool {
         matches!(*self, DiffStep::Move(_))
     }
+
+    pub fn is_shape_change(&self) -> bool {
+        matches!(*self, DiffStep::ChangeShape(_))
+    }
 }
@@ -0,0 +1,73 @@
+//! Custom matching logic, that is combined with the configured algorithm.
+
+use crate::diff::matching_ids::{can_match, Anchors};
+use crate::errors::*;
+use crate::SVG;
+use error_chain::bail;
+use flange_flat_tree::Tree;
+
+/// Finds matching elements in two SVGs with domain specific knowledge.
+///
+/// The matches of the strategy are used before the configured algorithm
+/// (see `Config::algorithm`) matches the remaining elements,
+/// so a strategy only has to find the matches the built-in rules miss.
+/// The ids, moves, adds, removes and changes are derived from all matches.
+///
+/// The elements are given by their positions (see `SVG::positions` and `SVG::tag`).
+pub trait MatchingStrategy {
+    /// The pairs of matching elements, as positions in `origin` and `target`.
+    /// Every element can be in at most one pair.
+    fn find_matches(&self, origin: &SVG, target: &SVG) -> Vec<(usize, usize)>;
+
+    /// The name reported for the matches of the strategy (see `MatchedPair::rule`).
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// The matches of the strategy as anchors.
+///
+/// # Errors
+///
+/// If a position is not in the SVG, an element is matched more than once,
+/// a root element is matched with another element or elements with different
+/// tag names are matched, that are not both basic shapes.
+pub(crate) fn strategy_anchors(
+    strategy: &dyn MatchingStrategy,
+    origin: &SVG,
+    target: &SVG,
+) -> Result<Anchors> {
+    let mut res = Anchors::default();
+    let roots = (origin.root_position(), target.root_position());
+    for (o_index, t_index) in strategy.find_matches(origin, target) {
+        if o_index >= origin.tags.node_count() || t_index >= target.tags.node_count() {
+            bail!(ErrorKind::InvalidMatch(
+                o_index,
+                t_index,
+                "there is no element at this position".to_string()
+            ));
+        }
+        if (o_index == roots.0) != (t_index == roots.1) {
+            bail!(ErrorKind::InvalidMatch(
+                o_index,
+                t_index,
+                "the root elements are always matched with each other".to_string()
+            ));
+        }
+        if !can_match(origin.tag(o_index), target.tag(t_index)) {
+            bail!(ErrorKind::InvalidMatch(
+                o_index,
+                t_index,
+                "only basic shapes can be matched with elements with another tag name".to_string()
+            ));
+        }
+        if !res.insert(o_index, t_index, strategy.name()) {
+            bail!(ErrorKind::InvalidMatch(
+                o_index,
+                t_index,
+                "one of the elements is already matched".to_string()
+            ));
+        }
+    }
+    Ok(res)
+}
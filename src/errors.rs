@@ -12,4 +12,63 @@ error_chain! {
         SVGTYPE(::svgtypes::Error);
         SVGTYPEVIEWBOX(::svgtypes::ViewBoxError);
     }
+
+    errors {
+        DuplicateKey(attr: String, value: String) {
+            description("duplicate key")
+            display("the key {}=\"{}\" is used by more than one element", attr, value)
+        }
+        UnknownRule(name: String, referenced_by: String) {
+            description("unknown rule")
+            display("unknown rule {} (in {})", name, referenced_by)
+        }
+        UnknownAttributeGroup(name: String, rule: String) {
+            description("unknown attribute group")
+            display("unknown attribute group {} (in rule {})", name, rule)
+        }
+        DuplicateRule(name: String) {
+            description("duplicate rule")
+            display("the rule name {} is used more than once (or by a built-in rule)", name)
+        }
+        RuleCycle(rules: String) {
+            description("rules depend on each other in a cycle")
+            display("rules depend on each other in a cycle: {}", rules)
+        }
+        UnknownPreset(name: String) {
+            description("unknown preset")
+            display("unknown preset {}", name)
+        }
+        InvalidSelector(selector: String, reason: String) {
+            description("invalid selector")
+            display("invalid selector \"{}\": {}", selector, reason)
+        }
+        InvalidCost(name: String, value: f64) {
+            description("invalid edit cost")
+            display("the {} cost must be a non-negative number, not {}", name, value)
+        }
+        InvalidRatio(name: String, value: f64) {
+            description("invalid ratio")
+            display("{} must be between 0 and 1, not {}", name, value)
+        }
+        InvalidWeight(name: String, value: f64) {
+            description("invalid weight")
+            display("{} must be a non-negative number, not {}", name, value)
+        }
+        InvalidScale(name: String, value: f64) {
+            description("invalid scale")
+            display("{} must be a positive number, not {}", name, value)
+        }
+        InvalidIdPrefix(prefix: String) {
+            description("invalid id prefix")
+            display("the id prefix \"{}\" may only contain letters, digits, - and _", prefix)
+        }
+        InvalidIdAttribute(name: String) {
+            description("invalid id attribute")
+            display("\"{}\" is not a valid attribute name for the ids", name)
+        }
+        InvalidMatch(origin: usize, target: usize, reason: String) {
+            description("invalid match")
+            display("the elements {} and {} can not be matched: {}", origin, target, reason)
+        }
+    }
 }
@@ -36,9 +36,18 @@ mod errors;
 mod svg_data;
 
 pub use svg_data::print_svg;
+pub use svg_data::Tag;
 pub use svg_data::SVG;
 
 pub use self::diff::diff;
 pub use self::diff::diff_from_strings;
+pub use self::diff::diff_from_strings_with_lifetimes;
+pub use self::diff::diff_from_strings_with_reports;
+pub use self::diff::diff_with_report;
+pub use self::diff::diff_with_strategy;
 pub use self::diff::diffs;
+pub use self::diff::diffs_with_lifetimes;
 pub use self::diff::DiffStep;
+pub use self::diff::ElementLifetime;
+pub use self::diff::MatchingStrategy;
+pub use self::diff::{MatchedPair, MatchingReport, NearMiss, UnmatchedElement};
@@ -37,6 +37,14 @@ impl MatrixValue {
             f: t.f,
         })
     }
+
+    /// Transforms a point with this matrix.
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
 }
 
 // Don't use for indexing hash maps!
@@ -55,7 +55,21 @@ impl SVGAttValue {
         }
     }
 
-    pub fn hash_with_modifier<H: Hasher>(&self, with_pos: bool, _with_style: bool, hasher: &mut H) {
+    /// Splits a `style` attribute into its properties.
+    /// Returns the (property, value) pairs in the order they appear.
+    pub fn style_properties(&self) -> Vec<(String, String)> {
+        match &self {
+            SVGAttValue::String(s) => s
+                .split(';')
+                .filter_map(|decl| decl.split_once(':'))
+                .map(|(prop, value)| (prop.trim().to_string(), value.trim().to_string()))
+                .filter(|(prop, _)| !prop.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn hash_with_modifier<H: Hasher>(&self, with_pos: bool, hasher: &mut H) {
         match &self {
             SVGAttValue::String(s) => s.hash(hasher),
             SVGAttValue::Matrix(m) => m.hash(hasher),
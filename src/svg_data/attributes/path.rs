@@ -102,7 +102,7 @@ impl PathValue {
                     (
                         PathSegment::VerticalLineTo {
                             abs: false,
-                            y: y - last_pos.0,
+                            y: y - last_pos.1,
                         },
                         (last_pos.0, y),
                     )
@@ -196,8 +196,8 @@ impl PathValue {
                     (
                         PathSegment::EllipticalArc {
                             abs: false,
-                            rx: rx - last_pos.0,
-                            ry: ry - last_pos.1,
+                            rx,
+                            ry,
                             x_axis_rotation,
                             large_arc,
                             sweep,
@@ -220,6 +220,151 @@ impl PathValue {
         }
     }
 
+    /// All absolute points (end points and control points) of the path.
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        fn offset(abs: bool, pos: (f64, f64), x: f64, y: f64) -> (f64, f64) {
+            if abs {
+                (x, y)
+            } else {
+                (pos.0 + x, pos.1 + y)
+            }
+        }
+        let mut res = Vec::new();
+        let mut pos = (0.0, 0.0);
+        let mut start = (0.0, 0.0);
+        for seg in self.segments.iter() {
+            match *seg {
+                PathSegment::MoveTo { abs, x, y } => {
+                    pos = offset(abs, pos, x, y);
+                    start = pos;
+                    res.push(pos);
+                }
+                PathSegment::LineTo { abs, x, y }
+                | PathSegment::SmoothQuadratic { abs, x, y }
+                | PathSegment::EllipticalArc { abs, x, y, .. } => {
+                    pos = offset(abs, pos, x, y);
+                    res.push(pos);
+                }
+                PathSegment::HorizontalLineTo { abs, x } => {
+                    pos = (if abs { x } else { pos.0 + x }, pos.1);
+                    res.push(pos);
+                }
+                PathSegment::VerticalLineTo { abs, y } => {
+                    pos = (pos.0, if abs { y } else { pos.1 + y });
+                    res.push(pos);
+                }
+                PathSegment::CurveTo {
+                    abs,
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    x,
+                    y,
+                } => {
+                    res.push(offset(abs, pos, x1, y1));
+                    res.push(offset(abs, pos, x2, y2));
+                    pos = offset(abs, pos, x, y);
+                    res.push(pos);
+                }
+                PathSegment::SmoothCurveTo { abs, x2, y2, x, y }
+                | PathSegment::Quadratic {
+                    abs,
+                    x1: x2,
+                    y1: y2,
+                    x,
+                    y,
+                } => {
+                    res.push(offset(abs, pos, x2, y2));
+                    pos = offset(abs, pos, x, y);
+                    res.push(pos);
+                }
+                PathSegment::ClosePath { .. } => {
+                    pos = start;
+                }
+            }
+        }
+        res
+    }
+
+    /// What the points of the path don't describe: the commands (with the rotation
+    /// and the flags of arcs) and the radii of the arcs, as points relative
+    /// to the start of the arcs (so they are moved and scaled with the path).
+    pub fn shape_details(&self) -> (String, Vec<(f64, f64)>) {
+        let mut commands = String::new();
+        let mut radii = Vec::new();
+        // The end points of the segments (see `points`)
+        let mut pos = (0.0, 0.0);
+        let mut start = (0.0, 0.0);
+        for seg in self.segments.iter() {
+            let current = pos;
+            let end = |abs: bool, x: f64, y: f64| {
+                if abs {
+                    (x, y)
+                } else {
+                    (current.0 + x, current.1 + y)
+                }
+            };
+            match *seg {
+                PathSegment::MoveTo { abs, x, y } => {
+                    commands.push('m');
+                    pos = end(abs, x, y);
+                    start = pos;
+                }
+                PathSegment::LineTo { abs, x, y } => {
+                    commands.push('l');
+                    pos = end(abs, x, y);
+                }
+                PathSegment::HorizontalLineTo { abs, x } => {
+                    commands.push('h');
+                    pos = (if abs { x } else { pos.0 + x }, pos.1);
+                }
+                PathSegment::VerticalLineTo { abs, y } => {
+                    commands.push('v');
+                    pos = (pos.0, if abs { y } else { pos.1 + y });
+                }
+                PathSegment::CurveTo { abs, x, y, .. } => {
+                    commands.push('c');
+                    pos = end(abs, x, y);
+                }
+                PathSegment::SmoothCurveTo { abs, x, y, .. } => {
+                    commands.push('s');
+                    pos = end(abs, x, y);
+                }
+                PathSegment::Quadratic { abs, x, y, .. } => {
+                    commands.push('q');
+                    pos = end(abs, x, y);
+                }
+                PathSegment::SmoothQuadratic { abs, x, y } => {
+                    commands.push('t');
+                    pos = end(abs, x, y);
+                }
+                PathSegment::EllipticalArc {
+                    abs,
+                    rx,
+                    ry,
+                    x_axis_rotation,
+                    large_arc,
+                    sweep,
+                    x,
+                    y,
+                } => {
+                    commands.push_str(&format!(
+                        "a{:.4} {}{}",
+                        x_axis_rotation, large_arc as u8, sweep as u8
+                    ));
+                    radii.push((pos.0 + rx, pos.1 + ry));
+                    pos = end(abs, x, y);
+                }
+                PathSegment::ClosePath { .. } => {
+                    commands.push('z');
+                    pos = start;
+                }
+            }
+        }
+        (commands, radii)
+    }
+
     pub fn hash_with_modifier<H: Hasher>(&self, with_pos: bool, hasher: &mut H) {
         self.to_hashable_string(with_pos).hash(hasher);
     }
@@ -398,3 +543,29 @@ impl Display for PathValue {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relative_segments() {
+        let absolute = PathValue::from_string("M 10 10 V 20 A 5 5 0 0 1 20 20").unwrap();
+        let relative = PathValue::from_string("M 10 10 v 10 a 5 5 0 0 1 10 0").unwrap();
+        assert_eq!(absolute, relative);
+        assert_eq!(
+            absolute.to_hashable_string(false),
+            "v 10.00000000a 5.00000000 5.00000000 0.00000000 0 1 10.00000000 0.00000000"
+        );
+        // Only the start differs
+        let moved = PathValue::from_string("M 30 0 V 10 A 5 5 0 0 1 40 10").unwrap();
+        assert_eq!(
+            absolute.to_hashable_string(false),
+            moved.to_hashable_string(false)
+        );
+        assert_ne!(
+            absolute.to_hashable_string(true),
+            moved.to_hashable_string(true)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Extraction of the geometry of SVG elements.
+//!
+//! The geometry of an element is described by a list of points (corners,
+//! end points, control points ...) in the coordinate system of the parent.
+//! This allows to compare the shape of elements independent of where
+//! they have been placed (see `GeometryMode`).
+
+use crate::config::GeometryMode;
+use crate::svg_data::attributes::SVGAttValue;
+use crate::svg_data::outline::Outline;
+use crate::svg_data::Tag;
+use crate::SVG;
+use flange_flat_tree::{Subtree, Tree};
+use std::str::FromStr;
+
+/// Attributes that describe the position and size of an element.
+pub const GEOMETRY_ATTRIBUTES: [&str; 18] = [
+    "x",
+    "y",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "width",
+    "height",
+    "points",
+    "d",
+    "dx",
+    "dy",
+    "transform",
+];
+
+/// Attributes that describe the outline of basic shapes (see `canonical_outline`).
+pub const SHAPE_ATTRIBUTES: [&str; 15] = [
+    "x", "y", "cx", "cy", "r", "rx", "ry", "x1", "y1", "x2", "y2", "width", "height", "points", "d",
+];
+
+/// The magic number to approximate a quarter ellipse with a cubic bezier curve.
+const KAPPA: f64 = 0.552_284_75;
+
+/// Reads a numeric attribute.
+/// For list values (like `dx="1 2 3"`) the first number is returned.
+pub(crate) fn number_attr(tag: &Tag, name: &str) -> Option<f64> {
+    let value = tag.args.get(name)?.to_string();
+    let first = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .find(|s| !s.is_empty())?;
+    svgtypes::Length::from_str(first).ok().map(|l| l.number)
+}
+
+fn parse_points(value: &str) -> Vec<(f64, f64)> {
+    let numbers: Vec<f64> = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| f64::from_str(s).ok())
+        .collect();
+    numbers.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+}
+
+/// The points describing the shape of the tag in its own coordinate system.
+fn local_shape_points(tag: &Tag) -> Vec<(f64, f64)> {
+    let num = |name: &str| number_attr(tag, name);
+    match tag.name.as_str() {
+        "rect" => {
+            let (x, y) = (num("x").unwrap_or(0.0), num("y").unwrap_or(0.0));
+            let (w, h) = (num("width").unwrap_or(0.0), num("height").unwrap_or(0.0));
+            vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (num("cx").unwrap_or(0.0), num("cy").unwrap_or(0.0));
+            let rx = num("rx").or_else(|| num("r")).unwrap_or(0.0);
+            let ry = num("ry").or_else(|| num("r")).unwrap_or(0.0);
+            vec![(cx - rx, cy - ry), (cx + rx, cy + ry)]
+        }
+        "line" => vec![
+            (num("x1").unwrap_or(0.0), num("y1").unwrap_or(0.0)),
+            (num("x2").unwrap_or(0.0), num("y2").unwrap_or(0.0)),
+        ],
+        "polyline" | "polygon" => tag
+            .args
+            .get("points")
+            .map(|p| parse_points(&p.to_string()))
+            .unwrap_or_default(),
+        "path" => match tag.args.get("d") {
+            Some(SVGAttValue::Path(p)) => p.points(),
+            _ => Vec::new(),
+        },
+        _ => {
+            // Text, images, use ... are positioned by x/y and optionally sized.
+            let mut res = Vec::new();
+            if tag.args.contains_key("x") || tag.args.contains_key("y") {
+                let x = num("x").unwrap_or(0.0) + num("dx").unwrap_or(0.0);
+                let y = num("y").unwrap_or(0.0) + num("dy").unwrap_or(0.0);
+                res.push((x, y));
+                if let (Some(w), Some(h)) = (num("width"), num("height")) {
+                    res.push((x + w, y + h));
+                }
+            }
+            res
+        }
+    }
+}
+
+/// What the points of the shape don't describe (see `local_shape_points`):
+/// the commands of paths and the corner and arc radii, as points that
+/// are moved and scaled with the shape.
+fn local_shape_details(tag: &Tag) -> (String, Vec<(f64, f64)>) {
+    let num = |name: &str| number_attr(tag, name);
+    match tag.name.as_str() {
+        "rect" => {
+            let (x, y) = (num("x").unwrap_or(0.0), num("y").unwrap_or(0.0));
+            let (w, h) = (num("width").unwrap_or(0.0), num("height").unwrap_or(0.0));
+            let rx = num("rx").or_else(|| num("ry")).unwrap_or(0.0).min(w / 2.0);
+            let ry = num("ry").or_else(|| num("rx")).unwrap_or(0.0).min(h / 2.0);
+            (String::new(), vec![(x + rx, y + ry)])
+        }
+        "path" => match tag.args.get("d") {
+            Some(SVGAttValue::Path(p)) => p.shape_details(),
+            _ => (String::new(), Vec::new()),
+        },
+        _ => (String::new(), Vec::new()),
+    }
+}
+
+/// Applies the `transform` of the tag to the points.
+fn transform_points(tag: &Tag, points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    if let Some(SVGAttValue::Matrix(m)) = tag.args.get("transform") {
+        points.into_iter().map(|p| m.apply(p)).collect()
+    } else {
+        points
+    }
+}
+
+/// The points describing the shape of the tag, in the coordinate system
+/// of the parent (so the `transform` of the tag is applied).
+pub(crate) fn shape_points(tag: &Tag) -> Vec<(f64, f64)> {
+    transform_points(tag, local_shape_points(tag))
+}
+
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    /// The smallest box containing all points, `None` if there are no points.
+    pub fn from_points(points: &[(f64, f64)]) -> Option<BoundingBox> {
+        if points.is_empty() {
+            return None;
+        }
+        Some(BoundingBox {
+            min_x: points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min),
+            min_y: points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min),
+            max_x: points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max),
+            max_y: points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        [
+            (self.min_x, self.min_y),
+            (self.max_x, self.min_y),
+            (self.max_x, self.max_y),
+            (self.min_x, self.max_y),
+        ]
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (
+            (self.min_x + self.max_x) / 2.0,
+            (self.min_y + self.max_y) / 2.0,
+        )
+    }
+
+    pub fn area(&self) -> f64 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+
+    /// How much the boxes overlap, as intersection over union.
+    pub fn overlap(&self, o: &BoundingBox) -> f64 {
+        let w = (self.max_x.min(o.max_x) - self.min_x.max(o.min_x)).max(0.0);
+        let h = (self.max_y.min(o.max_y) - self.min_y.max(o.min_y)).max(0.0);
+        let intersection = w * h;
+        let union = self.area() + o.area() - intersection;
+        if union > 0.0 {
+            intersection / union
+        } else if self == o {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Distance between the centers of the boxes.
+    pub fn center_distance(&self, o: &BoundingBox) -> f64 {
+        let (a, b) = (self.center(), o.center());
+        ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+    }
+}
+
+/// The bounding boxes of all elements (including their children) in the
+/// coordinate system of their parents, indexed like the tags of the svg.
+/// Elements without any geometry have no bounding box.
+pub(crate) fn bounding_boxes(svg: &SVG) -> Vec<Option<BoundingBox>> {
+    let mut res = vec![None; svg.tags.node_count()];
+    bounding_boxes_rec(svg, svg.tags.root().get_pos(), &mut res);
+    res
+}
+
+fn bounding_boxes_rec(svg: &SVG, pos: usize, res: &mut [Option<BoundingBox>]) {
+    let tag = svg.tags.at_pos(pos).value();
+    let mut points = local_shape_points(tag);
+    for &child in svg.tags.get_nav().children(pos).iter() {
+        bounding_boxes_rec(svg, child, res);
+        if let Some(child_box) = res[child] {
+            points.extend(child_box.corners());
+        }
+    }
+    res[pos] = BoundingBox::from_points(&transform_points(tag, points));
+}
+
+/// Whether the tag is a basic shape, that can be drawn as a path.
+pub(crate) fn is_basic_shape(name: &str) -> bool {
+    matches!(
+        name,
+        "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" | "path"
+    )
+}
+
+/// The outline of a basic shape, `None` for other tags.
+///
+/// All segments are cubic bezier curves in absolute coordinates (see `Outline`),
+/// so the outlines of all shapes can be morphed into each other.
+fn canonical_outline(tag: &Tag) -> Option<Outline> {
+    let num = |name: &str| number_attr(tag, name);
+    let mut res = Outline::default();
+    match tag.name.as_str() {
+        "rect" => {
+            let (x, y) = (num("x").unwrap_or(0.0), num("y").unwrap_or(0.0));
+            let (w, h) = (num("width").unwrap_or(0.0), num("height").unwrap_or(0.0));
+            let rx = num("rx").or_else(|| num("ry")).unwrap_or(0.0).min(w / 2.0);
+            let ry = num("ry").or_else(|| num("rx")).unwrap_or(0.0).min(h / 2.0);
+            if rx <= 0.0 || ry <= 0.0 {
+                res.move_to((x, y));
+                res.line_to((x + w, y));
+                res.line_to((x + w, y + h));
+                res.line_to((x, y + h));
+            } else {
+                let (kx, ky) = (KAPPA * rx, KAPPA * ry);
+                let (r, b) = (x + w, y + h);
+                res.move_to((x + rx, y));
+                res.line_to((r - rx, y));
+                res.curve_to((r - rx + kx, y), (r, y + ry - ky), (r, y + ry));
+                res.line_to((r, b - ry));
+                res.curve_to((r, b - ry + ky), (r - rx + kx, b), (r - rx, b));
+                res.line_to((x + rx, b));
+                res.curve_to((x + rx - kx, b), (x, b - ry + ky), (x, b - ry));
+                res.line_to((x, y + ry));
+                res.curve_to((x, y + ry - ky), (x + rx - kx, y), (x + rx, y));
+            }
+            res.close();
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (num("cx").unwrap_or(0.0), num("cy").unwrap_or(0.0));
+            let rx = num("rx").or_else(|| num("r")).unwrap_or(0.0);
+            let ry = num("ry").or_else(|| num("r")).unwrap_or(0.0);
+            let (kx, ky) = (KAPPA * rx, KAPPA * ry);
+            res.move_to((cx + rx, cy));
+            res.curve_to((cx + rx, cy + ky), (cx + kx, cy + ry), (cx, cy + ry));
+            res.curve_to((cx - kx, cy + ry), (cx - rx, cy + ky), (cx - rx, cy));
+            res.curve_to((cx - rx, cy - ky), (cx - kx, cy - ry), (cx, cy - ry));
+            res.curve_to((cx + kx, cy - ry), (cx + rx, cy - ky), (cx + rx, cy));
+            res.close();
+        }
+        "line" | "polyline" | "polygon" => {
+            let points = if tag.name == "line" {
+                local_shape_points(tag)
+            } else {
+                tag.args
+                    .get("points")
+                    .map(|p| parse_points(&p.to_string()))
+                    .unwrap_or_default()
+            };
+            let (first, rest) = points.split_first()?;
+            res.move_to(*first);
+            for point in rest {
+                res.line_to(*point);
+            }
+            if tag.name == "polygon" {
+                res.close();
+            }
+        }
+        "path" => return Outline::from_path_data(&tag.args.get("d")?.to_string()),
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// The outlines of two basic shapes as path data (empty for other tags),
+/// with the same number of subpaths and cubic curves, so they can be morphed into each other.
+pub(crate) fn canonical_paths(origin: &Tag, target: &Tag) -> (String, String) {
+    match (canonical_outline(origin), canonical_outline(target)) {
+        (Some(mut origin), Some(mut target)) => {
+            Outline::match_segments(&mut origin, &mut target);
+            (origin.to_string(), target.to_string())
+        }
+        (origin, target) => (
+            origin.map(|o| o.to_string()).unwrap_or_default(),
+            target.map(|o| o.to_string()).unwrap_or_default(),
+        ),
+    }
+}
+
+/// The points of the canonical path of a basic shape, in the coordinate system of the parent.
+fn canonical_points(tag: &Tag) -> Vec<(f64, f64)> {
+    let points = canonical_outline(tag)
+        .map(|o| o.points())
+        .unwrap_or_default();
+    transform_points(tag, points)
+}
+
+/// A string describing the points and the detail points, normalized according to `mode`.
+///
+/// With `GeometryMode::Translation` the points are moved so that the
+/// bounding box starts at the origin, with `GeometryMode::TranslationAndScale`
+/// they are additionally scaled so that the bigger side of the bounding box is 1.
+/// The detail points are moved and scaled the same way, but are not part of the bounding box.
+fn signature(points: &[(f64, f64)], details: &[(f64, f64)], mode: GeometryMode) -> String {
+    let (offset, scale) = match BoundingBox::from_points(points) {
+        Some(bbox) if mode != GeometryMode::Exact => {
+            let size = (bbox.max_x - bbox.min_x).max(bbox.max_y - bbox.min_y);
+            if mode == GeometryMode::TranslationAndScale && size > 0.0 {
+                ((bbox.min_x, bbox.min_y), 1.0 / size)
+            } else {
+                ((bbox.min_x, bbox.min_y), 1.0)
+            }
+        }
+        _ => ((0.0, 0.0), 1.0),
+    };
+    points
+        .iter()
+        .chain(details.iter())
+        .map(|(x, y)| {
+            format!(
+                "{:.4} {:.4}",
+                (x - offset.0) * scale,
+                (y - offset.1) * scale
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// A string describing the shape of the tag, normalized according to `mode` (see `signature`).
+pub(crate) fn shape_signature(tag: &Tag, mode: GeometryMode) -> String {
+    let (commands, details) = local_shape_details(tag);
+    let details = transform_points(tag, details);
+    format!(
+        "{}|{}",
+        commands,
+        signature(&shape_points(tag), &details, mode)
+    )
+}
+
+/// Like `shape_signature`, but for the canonical path of a basic shape,
+/// so that it is the same for all shapes with the same outline.
+pub(crate) fn canonical_shape_signature(tag: &Tag, mode: GeometryMode) -> String {
+    signature(&canonical_points(tag), &[], mode)
+}
@@ -1,9 +1,18 @@
 pub mod attributes;
+mod geometry;
+mod outline;
 mod printer;
+mod references;
 mod svg;
 mod tag;
 mod treehash;
 
+pub(crate) use self::geometry::bounding_boxes;
+pub(crate) use self::geometry::canonical_paths;
+pub(crate) use self::geometry::is_basic_shape;
+pub(crate) use self::geometry::BoundingBox;
+pub(crate) use self::geometry::GEOMETRY_ATTRIBUTES;
+pub(crate) use self::geometry::SHAPE_ATTRIBUTES;
 pub use self::svg::SVGWithIDs;
 pub(crate) use self::svg::SVGWithMatchingState;
 pub use self::svg::SVGWithTreeHash;
@@ -11,4 +20,5 @@ pub use self::svg::SVGWithTreeHashSubtree;
 pub use self::svg::SVG;
 pub use self::tag::Tag;
 pub use printer::*;
+pub(crate) use treehash::HashContext;
 pub(crate) use treehash::TreeHash;
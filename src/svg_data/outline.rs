@@ -0,0 +1,413 @@
+//! Outlines of shapes as absolute cubic bezier curves.
+//!
+//! Animations can only morph between paths with the same commands, so all
+//! segments (lines, quadratic curves, arcs ...) are turned into cubic curves,
+//! and the outlines of two shapes can be split until they have the same number of them.
+
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use svgtypes::{PathParser, PathSegment};
+
+type Point = (f64, f64);
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// The point mirrored at `center`.
+fn reflect(point: Point, center: Point) -> Point {
+    (2.0 * center.0 - point.0, 2.0 * center.1 - point.1)
+}
+
+/// A subpath, beginning at `start` with a curve (control points and end point)
+/// for every segment.
+#[derive(Debug, Clone, PartialEq)]
+struct Subpath {
+    start: Point,
+    curves: Vec<[Point; 3]>,
+    closed: bool,
+}
+
+impl Subpath {
+    fn new(start: Point) -> Subpath {
+        Subpath {
+            start,
+            curves: Vec::new(),
+            closed: false,
+        }
+    }
+
+    fn end(&self) -> Point {
+        self.curves.last().map(|c| c[2]).unwrap_or(self.start)
+    }
+
+    fn curve_start(&self, index: usize) -> Point {
+        if index == 0 {
+            self.start
+        } else {
+            self.curves[index - 1][2]
+        }
+    }
+
+    /// The length of the control polygon of the curve, which is at least the length of the curve.
+    fn curve_length(&self, index: usize) -> f64 {
+        let [c1, c2, end] = self.curves[index];
+        distance(self.curve_start(index), c1) + distance(c1, c2) + distance(c2, end)
+    }
+
+    /// Splits the curve in two halves.
+    fn split(&mut self, index: usize) {
+        let start = self.curve_start(index);
+        let [c1, c2, end] = self.curves[index];
+        let (a, b, c) = (lerp(start, c1, 0.5), lerp(c1, c2, 0.5), lerp(c2, end, 0.5));
+        let (ab, bc) = (lerp(a, b, 0.5), lerp(b, c, 0.5));
+        let middle = lerp(ab, bc, 0.5);
+        self.curves[index] = [a, ab, middle];
+        self.curves.insert(index + 1, [bc, c, end]);
+    }
+
+    /// Splits the longest curves until the subpath has `count` curves.
+    fn split_to(&mut self, count: usize) {
+        if self.curves.is_empty() && count > 0 {
+            let start = self.start;
+            self.curves.push([start, start, start]);
+        }
+        while self.curves.len() < count {
+            let longest = (0..self.curves.len())
+                .max_by(|a, b| self.curve_length(*a).total_cmp(&self.curve_length(*b)))
+                .unwrap();
+            self.split(longest);
+        }
+    }
+}
+
+/// The outline of a shape, made of subpaths with cubic curves in absolute coordinates.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Outline {
+    subpaths: Vec<Subpath>,
+}
+
+impl Outline {
+    /// The point where the next segment starts.
+    fn current(&self) -> Point {
+        self.subpaths.last().map(|s| s.end()).unwrap_or((0.0, 0.0))
+    }
+
+    /// The subpath segments are added to, a closed subpath is continued by
+    /// a new one at the same start point.
+    fn open_subpath(&mut self) -> &mut Subpath {
+        match self.subpaths.last() {
+            Some(last) if !last.closed => {}
+            Some(last) => {
+                let start = last.start;
+                self.subpaths.push(Subpath::new(start));
+            }
+            None => self.subpaths.push(Subpath::new((0.0, 0.0))),
+        }
+        self.subpaths.last_mut().unwrap()
+    }
+
+    pub(crate) fn move_to(&mut self, point: Point) {
+        self.subpaths.push(Subpath::new(point));
+    }
+
+    pub(crate) fn line_to(&mut self, point: Point) {
+        let current = self.current();
+        self.curve_to(
+            lerp(current, point, 1.0 / 3.0),
+            lerp(current, point, 2.0 / 3.0),
+            point,
+        );
+    }
+
+    pub(crate) fn curve_to(&mut self, c1: Point, c2: Point, point: Point) {
+        self.open_subpath().curves.push([c1, c2, point]);
+    }
+
+    fn quadratic_to(&mut self, control: Point, point: Point) {
+        let current = self.current();
+        self.curve_to(
+            lerp(current, control, 2.0 / 3.0),
+            lerp(point, control, 2.0 / 3.0),
+            point,
+        );
+    }
+
+    /// Adds an elliptical arc as curves of at most a quarter ellipse each.
+    fn arc_to(&mut self, radii: Point, rotation: f64, large_arc: bool, sweep: bool, point: Point) {
+        let current = self.current();
+        if current == point {
+            return;
+        }
+        let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+        if rx == 0.0 || ry == 0.0 {
+            self.line_to(point);
+            return;
+        }
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        // The end points relative to the center between them, without the rotation
+        let (dx, dy) = ((current.0 - point.0) / 2.0, (current.1 - point.1) / 2.0);
+        let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+        let scale = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if scale > 1.0 {
+            rx *= scale.sqrt();
+            ry *= scale.sqrt();
+        }
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+        let coef = sign * (numerator / denominator).max(0.0).sqrt();
+        let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+        let center = (
+            cos * cx1 - sin * cy1 + (current.0 + point.0) / 2.0,
+            sin * cx1 + cos * cy1 + (current.1 + point.1) / 2.0,
+        );
+        let angle = |u: Point, v: Point| (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1);
+        let start_vector = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let end_vector = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+        let start_angle = angle((1.0, 0.0), start_vector);
+        let mut sweep_angle = angle(start_vector, end_vector);
+        if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= 2.0 * PI;
+        } else if sweep && sweep_angle < 0.0 {
+            sweep_angle += 2.0 * PI;
+        }
+
+        let count = (sweep_angle.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+        let step = sweep_angle / count as f64;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        let on_arc = |t: f64| {
+            (
+                center.0 + rx * t.cos() * cos - ry * t.sin() * sin,
+                center.1 + rx * t.cos() * sin + ry * t.sin() * cos,
+            )
+        };
+        let tangent = |t: f64| {
+            (
+                -rx * t.sin() * cos - ry * t.cos() * sin,
+                -rx * t.sin() * sin + ry * t.cos() * cos,
+            )
+        };
+        for i in 0..count {
+            let (t1, t2) = (
+                start_angle + i as f64 * step,
+                start_angle + (i + 1) as f64 * step,
+            );
+            let (p1, d1, d2) = (on_arc(t1), tangent(t1), tangent(t2));
+            let p2 = if i + 1 == count { point } else { on_arc(t2) };
+            self.curve_to(
+                (p1.0 + k * d1.0, p1.1 + k * d1.1),
+                (p2.0 - k * d2.0, p2.1 - k * d2.1),
+                p2,
+            );
+        }
+    }
+
+    /// Closes the current subpath with a line to its start (if it doesn't end there).
+    pub(crate) fn close(&mut self) {
+        let start = match self.subpaths.last() {
+            Some(last) if !last.closed => last.start,
+            _ => return,
+        };
+        if self.current() != start {
+            self.line_to(start);
+        }
+        self.subpaths.last_mut().unwrap().closed = true;
+    }
+
+    /// The outline of path data, `None` if it can't be parsed.
+    pub(crate) fn from_path_data(d: &str) -> Option<Outline> {
+        let mut res = Outline::default();
+        // The second control point of the last cubic or the control point of the last quadratic curve
+        let mut last_cubic: Option<Point> = None;
+        let mut last_quadratic: Option<Point> = None;
+        for segment in PathParser::from(d) {
+            let segment = segment.ok()?;
+            let current = res.current();
+            let abs_point = |abs: bool, x: f64, y: f64| {
+                if abs {
+                    (x, y)
+                } else {
+                    (current.0 + x, current.1 + y)
+                }
+            };
+            let (mut cubic, mut quadratic) = (None, None);
+            match segment {
+                PathSegment::MoveTo { abs, x, y } => res.move_to(abs_point(abs, x, y)),
+                PathSegment::LineTo { abs, x, y } => res.line_to(abs_point(abs, x, y)),
+                PathSegment::HorizontalLineTo { abs, x } => {
+                    let x = if abs { x } else { current.0 + x };
+                    res.line_to((x, current.1))
+                }
+                PathSegment::VerticalLineTo { abs, y } => {
+                    let y = if abs { y } else { current.1 + y };
+                    res.line_to((current.0, y))
+                }
+                PathSegment::CurveTo {
+                    abs,
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    x,
+                    y,
+                } => {
+                    let c2 = abs_point(abs, x2, y2);
+                    res.curve_to(abs_point(abs, x1, y1), c2, abs_point(abs, x, y));
+                    cubic = Some(c2);
+                }
+                PathSegment::SmoothCurveTo { abs, x2, y2, x, y } => {
+                    let c1 = last_cubic.map_or(current, |c| reflect(c, current));
+                    let c2 = abs_point(abs, x2, y2);
+                    res.curve_to(c1, c2, abs_point(abs, x, y));
+                    cubic = Some(c2);
+                }
+                PathSegment::Quadratic { abs, x1, y1, x, y } => {
+                    let control = abs_point(abs, x1, y1);
+                    res.quadratic_to(control, abs_point(abs, x, y));
+                    quadratic = Some(control);
+                }
+                PathSegment::SmoothQuadratic { abs, x, y } => {
+                    let control = last_quadratic.map_or(current, |c| reflect(c, current));
+                    res.quadratic_to(control, abs_point(abs, x, y));
+                    quadratic = Some(control);
+                }
+                PathSegment::EllipticalArc {
+                    abs,
+                    rx,
+                    ry,
+                    x_axis_rotation,
+                    large_arc,
+                    sweep,
+                    x,
+                    y,
+                } => res.arc_to(
+                    (rx, ry),
+                    x_axis_rotation,
+                    large_arc,
+                    sweep,
+                    abs_point(abs, x, y),
+                ),
+                PathSegment::ClosePath { .. } => res.close(),
+            }
+            last_cubic = cubic;
+            last_quadratic = quadratic;
+        }
+        Some(res)
+    }
+
+    /// The start points, control points and end points of all curves.
+    pub(crate) fn points(&self) -> Vec<Point> {
+        self.subpaths
+            .iter()
+            .flat_map(|s| std::iter::once(s.start).chain(s.curves.iter().flatten().copied()))
+            .collect()
+    }
+
+    /// Changes both outlines (without changing their shape) so they have the same
+    /// number of subpaths and curves, which can be morphed into each other.
+    ///
+    /// Missing subpaths are added as points at the end of the outline, and the longest
+    /// curves are split in half until both subpaths have the same number of curves.
+    /// Subpaths are only closed if they are closed in both outlines.
+    pub(crate) fn match_segments(a: &mut Outline, b: &mut Outline) {
+        for outline in [&mut *a, &mut *b] {
+            if outline.subpaths.is_empty() {
+                outline.move_to((0.0, 0.0));
+            }
+        }
+        while a.subpaths.len() < b.subpaths.len() {
+            a.move_to(a.current());
+        }
+        while b.subpaths.len() < a.subpaths.len() {
+            b.move_to(b.current());
+        }
+        for (sa, sb) in a.subpaths.iter_mut().zip(b.subpaths.iter_mut()) {
+            let count = sa.curves.len().max(sb.curves.len());
+            sa.split_to(count);
+            sb.split_to(count);
+            let closed = sa.closed && sb.closed;
+            sa.closed = closed;
+            sb.closed = closed;
+        }
+    }
+}
+
+impl Display for Outline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let point = |p: Point| format!("{} {}", p.0, p.1);
+        let mut commands = Vec::new();
+        for subpath in &self.subpaths {
+            commands.push(format!("M {}", point(subpath.start)));
+            for [c1, c2, end] in &subpath.curves {
+                commands.push(format!("C {}, {}, {}", point(*c1), point(*c2), point(*end)));
+            }
+            if subpath.closed {
+                commands.push("Z".to_string());
+            }
+        }
+        write!(f, "{}", commands.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn curve_count(outline: &Outline) -> Vec<usize> {
+        outline.subpaths.iter().map(|s| s.curves.len()).collect()
+    }
+
+    #[test]
+    fn relative_and_smooth_commands() {
+        let absolute = Outline::from_path_data("M 10 10 L 20 10 L 20 20 Z").unwrap();
+        let relative = Outline::from_path_data("m 10 10 h 10 v 10 z").unwrap();
+        assert_eq!(absolute, relative);
+        let d = absolute.to_string();
+        assert!(d.starts_with("M 10 10 C "));
+        assert!(d.ends_with(", 10 10 Z"));
+        assert_eq!(d.matches('C').count(), 3);
+
+        let smooth = Outline::from_path_data("M 0 0 C 0 10 10 10 10 0 S 20 -10 20 0").unwrap();
+        assert_eq!(smooth.subpaths[0].curves[1][0], (10.0, -10.0));
+        let smooth = Outline::from_path_data("M 0 0 Q 5 10 10 0 T 20 0").unwrap();
+        let quadratic = Outline::from_path_data("M 0 0 Q 5 10 10 0 Q 15 -10 20 0").unwrap();
+        assert_eq!(smooth, quadratic);
+    }
+
+    #[test]
+    fn arcs() {
+        // A half circle from (0, 0) to (20, 0) through (10, 10)
+        let arc = Outline::from_path_data("M 0 0 A 10 10 0 0 0 20 0").unwrap();
+        assert_eq!(curve_count(&arc), vec![2]);
+        let [_, _, middle] = arc.subpaths[0].curves[0];
+        assert!(distance(middle, (10.0, 10.0)) < 1e-9);
+        assert_eq!(arc.subpaths[0].curves[1][2], (20.0, 0.0));
+        let other_side = Outline::from_path_data("M 0 0 a 10 10 0 0 1 20 0").unwrap();
+        let [_, _, middle] = other_side.subpaths[0].curves[0];
+        assert!(distance(middle, (10.0, -10.0)) < 1e-9);
+    }
+
+    #[test]
+    fn matching_segments() {
+        let mut triangle = Outline::from_path_data("M 0 0 L 10 0 L 5 10 Z").unwrap();
+        let mut square = Outline::from_path_data("M 0 0 L 10 0 L 10 10 L 0 10 Z").unwrap();
+        Outline::match_segments(&mut triangle, &mut square);
+        assert_eq!(curve_count(&triangle), vec![4]);
+        assert_eq!(curve_count(&square), vec![4]);
+        // The corners are kept, a long side was split in half
+        let ends: Vec<Point> = triangle.subpaths[0].curves.iter().map(|c| c[2]).collect();
+        assert_eq!(ends, vec![(10.0, 0.0), (5.0, 10.0), (2.5, 5.0), (0.0, 0.0)]);
+
+        let mut two = Outline::from_path_data("M 0 0 L 10 0 M 20 0 L 30 0 L 30 10").unwrap();
+        let mut one = Outline::from_path_data("M 0 0 L 10 10").unwrap();
+        Outline::match_segments(&mut two, &mut one);
+        assert_eq!(curve_count(&two), vec![1, 2]);
+        assert_eq!(curve_count(&one), vec![1, 2]);
+        assert_eq!(one.subpaths[1].start, (10.0, 10.0));
+    }
+}
@@ -1,25 +1,75 @@
 use flange_flat_tree::{Subtree, Tree};
+use std::collections::HashMap;
 use svg::node::Text;
 use svg::Node;
 
+use crate::svg_data::attributes::SVGAttValue;
+use crate::svg_data::references::rewrite_references;
 use crate::svg_data::svg::SVGWithIDs;
 
 use super::Tag;
 
+/// How the elements of a printed SVG are addressed.
+pub struct Addressing {
+    /// The attribute the ids are written to (see `IdOptions::attribute`).
+    attribute: String,
+    /// The ids that replace the `id` attributes of elements (by the `id` attribute).
+    /// References to these elements are rewritten to the new ids.
+    renamed: HashMap<String, String>,
+}
+
+impl Addressing {
+    /// The addressing of the elements in `svg`, with the ids written to `attribute`.
+    pub(crate) fn new(svg: &SVGWithIDs, attribute: &str) -> Addressing {
+        let mut renamed = HashMap::new();
+        // Only ids in the id attribute replace the existing ones
+        if attribute == "id" {
+            for pos in 0..svg.node_count() {
+                let (tag, id) = svg.at_pos(pos).value();
+                if let (Some(own_id), Some(id)) = (tag.args.get("id"), id) {
+                    let own_id = own_id.to_string();
+                    if &own_id != id {
+                        renamed.entry(own_id).or_insert_with(|| id.clone());
+                    }
+                }
+            }
+        }
+        Addressing {
+            attribute: attribute.to_string(),
+            renamed,
+        }
+    }
+
+    /// The value of the attribute as it is printed,
+    /// with the references to elements with new ids rewritten.
+    pub(crate) fn value(&self, name: &str, value: &SVGAttValue) -> String {
+        let value = value.to_string();
+        if name == "id" {
+            // Replaced by the new id when it is written to `id`
+            return self.renamed.get(&value).cloned().unwrap_or(value);
+        }
+        rewrite_references(name, &value, &self.renamed)
+    }
+}
+
 fn build_element<'a, ST: Subtree<Node = (&'a Tag, &'a Option<String>)>>(
     svg: &ST,
+    with_identified_children: bool,
+    addressing: &Addressing,
 ) -> svg::node::element::Element {
     let tag = svg.value().0;
     let id = svg.value().1;
     let mut el = svg::node::element::Element::new(&tag.name);
     for (name, value) in &tag.args {
-        el.assign(name, svg::node::Value::from(value.to_string()))
+        el.assign(name, svg::node::Value::from(addressing.value(name, value)))
     }
     if let Some(id) = id {
-        el.assign("id", id.clone());
+        el.assign(addressing.attribute.as_str(), id.clone());
     }
     for child in &svg.children() {
-        el.append(build_element(child));
+        if with_identified_children || child.value().1.is_none() {
+            el.append(build_element(child, with_identified_children, addressing));
+        }
     }
     if !tag.text.is_empty() {
         el.append(Text::new(&tag.text))
@@ -27,17 +77,17 @@ fn build_element<'a, ST: Subtree<Node = (&'a Tag, &'a Option<String>)>>(
     el
 }
 
-fn build_doc(svg: &SVGWithIDs) -> svg::Document {
+fn build_doc(svg: &SVGWithIDs, addressing: &Addressing) -> svg::Document {
     let mut doc = svg::Document::new();
     let (root_tag, root_id) = svg.root().value();
     for (name, value) in &root_tag.args {
-        doc.assign(name, svg::node::Value::from(value.to_string()))
+        doc.assign(name, svg::node::Value::from(addressing.value(name, value)))
     }
     if let Some(id) = root_id {
-        doc.assign("id", id.clone());
+        doc.assign(addressing.attribute.as_str(), id.clone());
     }
     for child in &svg.root().children() {
-        doc.append(build_element(child));
+        doc.append(build_element(child, true, addressing));
     }
     if !root_tag.text.is_empty() {
         doc.append(Text::new(&root_tag.text))
@@ -46,7 +96,17 @@ fn build_doc(svg: &SVGWithIDs) -> svg::Document {
 }
 
 pub fn print_svg(svg: &SVGWithIDs, overwrite_view_box: Option<&svgtypes::ViewBox>) -> String {
-    let mut doc = build_doc(svg);
+    print_svg_with_id_attribute(svg, overwrite_view_box, "id")
+}
+
+/// Like `print_svg`, but the ids are written to the given attribute
+/// (see `IdOptions::attribute`).
+pub fn print_svg_with_id_attribute(
+    svg: &SVGWithIDs,
+    overwrite_view_box: Option<&svgtypes::ViewBox>,
+    id_attribute: &str,
+) -> String {
+    let mut doc = build_doc(svg, &Addressing::new(svg, id_attribute));
     if let Some(view_box) = overwrite_view_box {
         doc.assign(
             "viewBox",
@@ -61,7 +121,17 @@ pub fn print_svg(svg: &SVGWithIDs, overwrite_view_box: Option<&svgtypes::ViewBox
 
 pub fn print_svg_element<'a, ST: Subtree<Node = (&'a Tag, &'a Option<String>)>>(
     svg: &ST,
+    addressing: &Addressing,
 ) -> String {
-    let doc = build_element(svg);
+    let doc = build_element(svg, true, addressing);
     doc.to_string()
 }
+
+/// Like `print_svg_element`, but without the children that have an id
+/// (they are added or moved into the element by diff steps of their own).
+pub(crate) fn print_new_svg_element<'a, ST: Subtree<Node = (&'a Tag, &'a Option<String>)>>(
+    svg: &ST,
+    addressing: &Addressing,
+) -> String {
+    build_element(svg, false, addressing).to_string()
+}
@@ -0,0 +1,85 @@
+//! References from attributes to the ids of other elements,
+//! like `fill="url(#gradient)"` or `href="#marker"`.
+
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Attributes with a list of ids (separated by whitespace).
+const ID_LIST_ATTRIBUTES: [&str; 8] = [
+    "aria-activedescendant",
+    "aria-controls",
+    "aria-describedby",
+    "aria-details",
+    "aria-errormessage",
+    "aria-flowto",
+    "aria-labelledby",
+    "aria-owns",
+];
+
+fn url_reference() -> &'static Regex {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    URL.get_or_init(|| Regex::new(r#"url\(\s*(['"]?)#([^'")\s]+)(['"]?)\s*\)"#).unwrap())
+}
+
+/// Replaces the references in the value of the attribute `name` to the ids
+/// in `renamed` by references to their new ids.
+///
+/// References are `url(#id)` (in any attribute, also in `style`), `#id` in
+/// `href` and `xlink:href`, and the ids in the ARIA attributes referring to elements.
+pub(crate) fn rewrite_references(
+    name: &str,
+    value: &str,
+    renamed: &HashMap<String, String>,
+) -> String {
+    if renamed.is_empty() {
+        return value.to_string();
+    }
+    if ID_LIST_ATTRIBUTES.contains(&name) {
+        return value
+            .split_whitespace()
+            .map(|id| renamed.get(id).map(|s| s.as_str()).unwrap_or(id))
+            .collect::<Vec<&str>>()
+            .join(" ");
+    }
+    if name == "href" || name == "xlink:href" {
+        if let Some(new_id) = value.strip_prefix('#').and_then(|id| renamed.get(id)) {
+            return format!("#{}", new_id);
+        }
+        return value.to_string();
+    }
+    url_reference()
+        .replace_all(value, |c: &Captures| match renamed.get(&c[2]) {
+            Some(new_id) => format!("url({}#{}{})", &c[1], new_id, &c[3]),
+            None => c[0].to_string(),
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rewrite() {
+        let renamed: HashMap<String, String> = [("a", "x"), ("b", "y")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(rewrite_references("fill", "url(#a)", &renamed), "url(#x)");
+        assert_eq!(
+            rewrite_references("style", "fill: url('#b'); stroke: url(#c)", &renamed),
+            "fill: url('#y'); stroke: url(#c)"
+        );
+        assert_eq!(rewrite_references("href", "#a", &renamed), "#x");
+        assert_eq!(
+            rewrite_references("xlink:href", "b.svg#a", &renamed),
+            "b.svg#a"
+        );
+        assert_eq!(
+            rewrite_references("aria-labelledby", "a c b", &renamed),
+            "x c y"
+        );
+        assert_eq!(rewrite_references("class", "a", &renamed), "a");
+    }
+}
@@ -1,11 +1,13 @@
 use error_chain::bail;
-use flange_flat_tree::Tree;
+use flange_flat_tree::{Subtree, Tree};
 use regex::RegexBuilder;
+use std::collections::HashMap;
 use svg::parser::Event;
 use svg::Parser;
 
 use super::Tag;
 use super::TreeHash;
+use crate::config::Ignore;
 use crate::diff::MatchingState;
 use crate::errors::*;
 use flange_flat_tree::Builder;
@@ -34,6 +36,139 @@ impl SVG {
         self.tags.flange(states)
     }
 
+    /// The position of the root element.
+    pub fn root_position(&self) -> usize {
+        self.tags.root().get_pos()
+    }
+
+    /// The positions of all elements, parents before their children (in document order).
+    pub fn positions(&self) -> Vec<usize> {
+        let mut res = Vec::new();
+        let mut stack = vec![self.root_position()];
+        while let Some(pos) = stack.pop() {
+            res.push(pos);
+            stack.extend(self.tags.get_nav().children(pos).iter().rev());
+        }
+        res
+    }
+
+    /// The element at `pos`.
+    pub fn tag(&self, pos: usize) -> &Tag {
+        self.tags.at_pos(pos).value()
+    }
+
+    /// The position of the parent of the element at `pos` (`None` for the root).
+    pub fn parent_position(&self, pos: usize) -> Option<usize> {
+        self.tags.at_pos(pos).parent().map(|p| p.get_pos())
+    }
+
+    /// The positions of the children of the element at `pos`, in document order.
+    pub fn child_positions(&self, pos: usize) -> Vec<usize> {
+        self.tags.get_nav().children(pos).to_vec()
+    }
+
+    /// The positions of the elements by their keys, the values of the `key_attributes`
+    /// (see `MatchingRules::key_attributes`).
+    ///
+    /// # Errors
+    ///
+    /// If a key is used by more than one element.
+    pub fn elements_by_key(
+        &self,
+        key_attributes: &[String],
+    ) -> Result<HashMap<(String, String), usize>> {
+        let mut res = HashMap::new();
+        for pos in 0..self.tags.node_count() {
+            let tag = self.tag(pos);
+            for attr in key_attributes {
+                if let Some(value) = tag.args.get(attr) {
+                    let key = (attr.clone(), value.to_string());
+                    if res.insert(key, pos).is_some() {
+                        bail!(ErrorKind::DuplicateKey(attr.clone(), value.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// The child indices from the root to the element at `pos`.
+    pub(crate) fn index_path(&self, pos: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        let mut current = self.tags.at_pos(pos);
+        while let Some(parent) = current.parent() {
+            let index = parent
+                .children()
+                .into_iter()
+                .position(|c| c.get_pos() == current.get_pos())
+                .unwrap_or(0);
+            res.push(index);
+            current = parent;
+        }
+        res.reverse();
+        res
+    }
+
+    /// The position of the element at the child indices `path` (see `index_path`).
+    pub(crate) fn position_at_path(&self, path: &[usize]) -> Option<usize> {
+        let mut pos = self.root_position();
+        for index in path {
+            pos = *self.tags.get_nav().children(pos).get(*index)?;
+        }
+        Some(pos)
+    }
+
+    /// The positions of the ancestors of the tag at `pos`, starting with the root.
+    pub(crate) fn ancestor_positions(&self, pos: usize) -> Vec<usize> {
+        let mut res = Vec::new();
+        let mut current = self.tags.at_pos(pos).parent();
+        while let Some(parent) = current {
+            res.push(parent.get_pos());
+            current = parent.parent();
+        }
+        res.reverse();
+        res
+    }
+
+    /// A copy without the ignored elements and attributes, and the position
+    /// in this SVG of every element in the copy.
+    pub(crate) fn without_ignored(&self, ignore: &Ignore) -> (SVG, Vec<usize>) {
+        let mut tags = Builder::new();
+        let mut positions = Vec::new();
+        self.copy_kept(
+            self.root_position(),
+            &mut Vec::new(),
+            ignore,
+            &mut tags,
+            &mut positions,
+        );
+        (SVG { tags: tags.build() }, positions)
+    }
+
+    fn copy_kept<'a>(
+        &'a self,
+        pos: usize,
+        ancestors: &mut Vec<&'a Tag>,
+        ignore: &Ignore,
+        tags: &mut Builder<Tag>,
+        positions: &mut Vec<usize>,
+    ) {
+        let tag = self.tag(pos);
+        if !ancestors.is_empty() && ignore.ignores_element(tag, ancestors) {
+            return;
+        }
+        let mut kept = tag.clone();
+        kept.args.retain(|name, _| !ignore.ignores_attribute(name));
+        tags.start_element(kept);
+        positions.push(pos);
+        ancestors.push(tag);
+        for child in self.child_positions(pos) {
+            self.copy_kept(child, ancestors, ignore, tags, positions);
+        }
+        ancestors.pop();
+        tags.end_element();
+    }
+
     pub fn parse_svg_string(input: &str) -> Result<SVG> {
         // Extract the svg part
         let re = RegexBuilder::new(r"<svg.*</svg>")
@@ -18,4 +18,19 @@ impl Tag {
         }
         Ok(Tag { name, text, args })
     }
+
+    /// The tag name, like `rect`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The text content of the element.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The value of the attribute, as it is printed.
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        self.args.get(name).map(|v| v.to_string())
+    }
 }
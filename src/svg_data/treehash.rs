@@ -7,8 +7,21 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 
+use super::geometry;
 use super::svg::SVGWithTreeHash;
 
+/// The hashes of the elements around an element, that rules can refer to
+/// (see `MatchingRule::prev_sibling_rule`, `MatchingRule::ancestor` ...).
+#[derive(Default)]
+pub struct HashContext<'a> {
+    /// The ancestors with their hashes, starting with the root.
+    pub ancestors: Vec<(&'a Tag, &'a TreeHash)>,
+    pub prev_sibling: Option<&'a TreeHash>,
+    pub next_sibling: Option<&'a TreeHash>,
+    /// The sibling the `sibling` of the rule refers to.
+    pub sibling: Option<&'a TreeHash>,
+}
+
 /** different hashes for comparing SVG tags.
 *  This allows fast comparision by tags and tag trees
 *  with different criteria.
@@ -29,15 +42,35 @@ impl TreeHash {
         for rule in rules {
             svg.tags.get_nav().for_each_depth_first(|i, _a| {
                 let tag = svg.tags.at_pos(i).value();
-                let prev_sibling = res.get_nav().prev_sibling(i).map(|s| res.get_flange(s));
-                let next_sibling = res.get_nav().next_sibling(i).map(|s| res.get_flange(s));
-                let children = res
-                    .get_nav()
-                    .children(i)
-                    .iter()
-                    .map(|s| res.get_flange(*s))
-                    .collect();
-                let val = TreeHash::calc_hash(rule, tag, &children, prev_sibling, next_sibling);
+                let nav = res.get_nav();
+                // The ancestors are only needed for selectors and ancestor rules
+                let ancestors = if rule.needs_ancestors() {
+                    svg.ancestor_positions(i)
+                        .into_iter()
+                        .map(|a| (svg.tags.at_pos(a).value(), res.get_flange(a)))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let sibling = rule.sibling.as_ref().and_then(|sibling| {
+                    let mut pos = Some(i);
+                    for _ in 0..sibling.offset.unsigned_abs() {
+                        pos = if sibling.offset < 0 {
+                            nav.prev_sibling(pos?)
+                        } else {
+                            nav.next_sibling(pos?)
+                        };
+                    }
+                    pos.map(|s| res.get_flange(s))
+                });
+                let context = HashContext {
+                    ancestors,
+                    prev_sibling: nav.prev_sibling(i).map(|s| res.get_flange(s)),
+                    next_sibling: nav.next_sibling(i).map(|s| res.get_flange(s)),
+                    sibling,
+                };
+                let children = nav.children(i).iter().map(|s| res.get_flange(*s)).collect();
+                let val = TreeHash::calc_hash(rule, tag, &context, &children);
                 if let Some(v) = val {
                     res.get_flange_mut(i).rules.insert(rule.name.clone(), v);
                 }
@@ -53,43 +86,66 @@ impl TreeHash {
     pub fn calc_hash(
         rule: &MatchingRule,
         tag: &Tag,
+        context: &HashContext,
         children: &Vec<&TreeHash>,
-        prev_sibling: Option<&TreeHash>,
-        next_sibling: Option<&TreeHash>,
     ) -> Option<u64> {
         // Check if the rule should be applied to us
-        if !rule.applies_to_tag(tag) {
+        let ancestors: Vec<&Tag> = context.ancestors.iter().map(|a| a.0).collect();
+        if !rule.applies_to(tag, &ancestors) {
             return None;
         }
         let mut hasher = DefaultHasher::new();
         // Check if the rule does not apply to use because we don't have the siblings
         if let Some(prev_rule) = &rule.prev_sibling_rule {
-            if let Some(value) = Self::get_sibling_value(&prev_sibling, prev_rule) {
+            if let Some(value) = Self::get_sibling_value(&context.prev_sibling, prev_rule) {
                 value.hash(&mut hasher);
             } else {
                 return None;
             }
         };
         if let Some(next_rule) = &rule.next_sibling_rule {
-            if let Some(value) = Self::get_sibling_value(&next_sibling, next_rule) {
+            if let Some(value) = Self::get_sibling_value(&context.next_sibling, next_rule) {
                 value.hash(&mut hasher);
             } else {
                 return None;
             }
         };
-        // Now apply the tag itself
-        tag.name.hash(&mut hasher);
+        // The same for the parent, ancestors and other siblings
+        if let Some(parent_rule) = &rule.parent_rule {
+            let (_, parent) = context.ancestors.last()?;
+            parent.rule_hash(parent_rule)?.hash(&mut hasher);
+        }
+        if let Some(ancestor) = &rule.ancestor {
+            let index = (0..ancestors.len())
+                .rev()
+                .find(|i| ancestor.selector.matches(ancestors[*i], &ancestors[..*i]))?;
+            context.ancestors[index]
+                .1
+                .rule_hash(&ancestor.rule)?
+                .hash(&mut hasher);
+        }
+        if let Some(sibling) = &rule.sibling {
+            Self::get_sibling_value(&context.sibling, &sibling.rule)?.hash(&mut hasher);
+        }
+        // Now apply the tag itself (basic shapes compared by their outline are all the same)
+        let canonical_shape = rule.uses_canonical_shape(tag);
+        if canonical_shape {
+            "shape".hash(&mut hasher);
+        } else {
+            tag.name.hash(&mut hasher);
+        }
         // Text?
-        if rule.include_text {
-            tag.text.hash(&mut hasher);
+        if rule.include_text && rule.fuzzy_text.is_none() {
+            rule.normalize_text(&tag.text).hash(&mut hasher);
         }
         // Children?
         if rule.recursive {
             // Which rule for children? Default to myself!
             let child_rule = rule.childrens_rule.as_ref().unwrap_or(&rule.name);
             let mut child_values = Vec::with_capacity(children.len());
+            // The rule does not apply if it does not apply to the children
             for child in children {
-                child_values.push(*child.rules.get(child_rule).unwrap());
+                child_values.push(*child.rules.get(child_rule)?);
             }
             // Should the children be sorted?
             if rule.sort_children {
@@ -102,13 +158,36 @@ impl TreeHash {
         // Sort the attributes
         let attritbutes = rule.included_sorted_attr(tag);
         for attribute in attritbutes {
-            tag.args.get(attribute).unwrap().hash_with_modifier(
-                rule.attr.as_ref().map(|a| a.with_pos).unwrap_or(false),
-                rule.attr.as_ref().map(|a| a.with_style).unwrap_or(false),
-                &mut hasher,
-            );
+            let value = tag.args.get(attribute).unwrap();
+            if attribute == "style" {
+                // Only hash the included properties
+                let mut properties = value.style_properties();
+                properties.retain(|(prop, _)| rule.style_property_is_included(prop));
+                for (prop, value) in properties.iter_mut() {
+                    if let Some(normalized) = rule.normalize_attr(prop, value) {
+                        *value = normalized;
+                    }
+                }
+                properties.sort();
+                properties.hash(&mut hasher);
+            } else if let Some(normalized) = rule.normalize_attr(attribute, &value.to_string()) {
+                normalized.hash(&mut hasher);
+            } else {
+                value.hash_with_modifier(
+                    rule.attr.as_ref().map(|a| a.with_pos).unwrap_or(false),
+                    &mut hasher,
+                );
+            }
             attribute.hash(&mut hasher);
         }
+        // The geometry, if it is not compared by the attributes
+        if canonical_shape {
+            if let Some(mode) = rule.canonical_shape_mode() {
+                geometry::canonical_shape_signature(tag, mode).hash(&mut hasher);
+            }
+        } else if let Some(mode) = rule.geometry_mode() {
+            geometry::shape_signature(tag, mode).hash(&mut hasher);
+        }
         Some(hasher.finish())
     }
 
@@ -123,22 +202,25 @@ impl TreeHash {
 
     fn new(tag: &Tag, children: &Vec<&TreeHash>) -> TreeHash {
         // Create the hasher
-        let all =
-            Self::calc_hash(&MatchingRule::new_all_rule(), tag, children, None, None).unwrap();
+        let all = Self::calc_hash(
+            &MatchingRule::new_all_rule(),
+            tag,
+            &HashContext::default(),
+            children,
+        )
+        .unwrap();
         let all_subtrees = Self::calc_hash(
             &MatchingRule::new_all_subtrees_rule(),
             tag,
+            &HashContext::default(),
             children,
-            None,
-            None,
         )
         .unwrap();
         let all_without_subtrees = Self::calc_hash(
             &MatchingRule::new_all_without_subtrees_rule(),
             tag,
+            &HashContext::default(),
             children,
-            None,
-            None,
         )
         .unwrap();
         let mut rules = HashMap::new();
@@ -158,6 +240,11 @@ impl TreeHash {
         self.all.eq(&o.all)
     }
 
+    /// The hash compared by `eq_all`, equal for identical subtrees.
+    pub(crate) fn all_hash(&self) -> u64 {
+        self.all
+    }
+
     pub fn eq_all_subtrees(&self, o: &TreeHash) -> bool {
         self.all_subtrees.eq(&o.all_subtrees)
     }
@@ -166,6 +253,11 @@ impl TreeHash {
         self.all_without_subtrees.eq(&o.all_without_subtrees)
     }
 
+    /// The hash by the rule, `None` if the rule does not apply.
+    pub fn rule_hash(&self, name: &str) -> Option<u64> {
+        self.rules.get(name).cloned()
+    }
+
     pub fn eq_rule(&self, name: &str, o: &TreeHash) -> bool {
         if let Some(my_value) = self.rules.get(name) {
             if let Some(o_value) = o.rules.get(name) {
@@ -186,7 +278,7 @@ impl Default for TreeHash {
 mod test {
     use super::*;
 
-    use crate::config::MatchingRules;
+    use crate::config::{GeometryMode, MatchingAttrRule, MatchingRules};
     use std::collections::HashMap;
     use svg::node::Value;
 
@@ -199,7 +291,12 @@ mod test {
     ) -> TreeHash {
         let mut res = TreeHash::new(tag, &children);
         for rule in rules {
-            let v = TreeHash::calc_hash(rule, tag, &children, prev, next);
+            let context = HashContext {
+                prev_sibling: prev,
+                next_sibling: next,
+                ..Default::default()
+            };
+            let v = TreeHash::calc_hash(rule, tag, &context, &children);
             if let Some(v) = v {
                 res.rules.insert(rule.name.clone(), v);
             }
@@ -359,4 +456,245 @@ mod test {
         assert!(a.eq_rule("with_reorder", &b));
         assert!(a.eq_rule("without_attr", &b));
     }
+
+    fn shape_rule(geometry: GeometryMode) -> MatchingRule {
+        let mut rule = MatchingRule::new_all_rule();
+        rule.name = "shape".to_string();
+        rule.attr = Some(MatchingAttrRule {
+            geometry,
+            ..Default::default()
+        });
+        rule
+    }
+
+    fn tag_with_args(name: &str, args: &[(&str, &str)]) -> Tag {
+        Tag::new(
+            name.to_string(),
+            "".to_string(),
+            args.iter()
+                .map(|(k, v)| (k.to_string(), Value::from(v.to_string())))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn moved_shapes() {
+        let line_a = tag_with_args(
+            "line",
+            &[("x1", "0"), ("y1", "0"), ("x2", "10"), ("y2", "5")],
+        );
+        let line_b = tag_with_args(
+            "line",
+            &[("x1", "20"), ("y1", "30"), ("x2", "30"), ("y2", "35")],
+        );
+        let poly_a = tag_with_args("polygon", &[("points", "0,0 10,0 5,5")]);
+        let poly_b = tag_with_args(
+            "polygon",
+            &[
+                ("points", "0,0 10,0 5,5"),
+                ("transform", "translate(40, 2)"),
+            ],
+        );
+        for (a, b) in [(&line_a, &line_b), (&poly_a, &poly_b)] {
+            let exact = shape_rule(GeometryMode::Exact);
+            let moved = shape_rule(GeometryMode::Translation);
+            assert_ne!(
+                TreeHash::calc_hash(&exact, a, &HashContext::default(), &vec![]),
+                TreeHash::calc_hash(&exact, b, &HashContext::default(), &vec![])
+            );
+            assert_eq!(
+                TreeHash::calc_hash(&moved, a, &HashContext::default(), &vec![]),
+                TreeHash::calc_hash(&moved, b, &HashContext::default(), &vec![])
+            );
+        }
+    }
+
+    #[test]
+    fn scaled_shapes() {
+        let small = tag_with_args("path", &[("d", "M 10 10 L 20 10 L 20 20 Z")]);
+        let big = tag_with_args("path", &[("d", "M 50 50 L 70 50 L 70 70 Z")]);
+        let moved = shape_rule(GeometryMode::Translation);
+        let scaled = shape_rule(GeometryMode::TranslationAndScale);
+        assert_ne!(
+            TreeHash::calc_hash(&moved, &small, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&moved, &big, &HashContext::default(), &vec![])
+        );
+        assert_eq!(
+            TreeHash::calc_hash(&scaled, &small, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&scaled, &big, &HashContext::default(), &vec![])
+        );
+    }
+
+    #[test]
+    fn rounded_rects() {
+        let square = tag_with_args("rect", &[("width", "10"), ("height", "10")]);
+        let rounded = tag_with_args("rect", &[("width", "10"), ("height", "10"), ("rx", "2")]);
+        let moved = tag_with_args(
+            "rect",
+            &[("x", "5"), ("width", "10"), ("height", "10"), ("rx", "2")],
+        );
+        let rule = shape_rule(GeometryMode::Translation);
+        let hash = |tag: &Tag| TreeHash::calc_hash(&rule, tag, &HashContext::default(), &vec![]);
+        assert_ne!(hash(&square), hash(&rounded));
+        assert_eq!(hash(&rounded), hash(&moved));
+    }
+
+    #[test]
+    fn path_commands() {
+        let line = tag_with_args("path", &[("d", "M 0 0 L 10 0")]);
+        let smooth = tag_with_args("path", &[("d", "M 0 0 T 10 0")]);
+        let arc = tag_with_args("path", &[("d", "M 0 0 A 5 5 0 0 1 10 0")]);
+        let large_arc = tag_with_args("path", &[("d", "M 0 0 A 5 5 0 1 1 10 0")]);
+        let wide_arc = tag_with_args("path", &[("d", "M 0 0 A 8 5 0 0 1 10 0")]);
+        let moved_arc = tag_with_args("path", &[("d", "M 20 20 A 5 5 0 0 1 30 20")]);
+        let relative_start = tag_with_args("path", &[("d", "m 10 10 h 5")]);
+        let absolute_start = tag_with_args("path", &[("d", "M 20 20 h 5")]);
+        let rule = shape_rule(GeometryMode::Translation);
+        let hash = |tag: &Tag| TreeHash::calc_hash(&rule, tag, &HashContext::default(), &vec![]);
+        assert_ne!(hash(&line), hash(&smooth));
+        assert_ne!(hash(&line), hash(&arc));
+        assert_ne!(hash(&arc), hash(&large_arc));
+        assert_ne!(hash(&arc), hash(&wide_arc));
+        assert_eq!(hash(&arc), hash(&moved_arc));
+        assert_eq!(hash(&relative_start), hash(&absolute_start));
+    }
+
+    #[test]
+    fn canonical_shapes() {
+        let rect = tag_with_args(
+            "rect",
+            &[("x", "10"), ("y", "10"), ("width", "10"), ("height", "5")],
+        );
+        let path = tag_with_args("path", &[("d", "M 10 10 h 10 v 5 H 10 z")]);
+        let moved_path = tag_with_args("path", &[("d", "M 30 0 L 40 0 L 40 5 L 30 5 Z")]);
+        let circle = tag_with_args("circle", &[("cx", "15"), ("cy", "12.5"), ("r", "5")]);
+        let mut exact = shape_rule(GeometryMode::Exact);
+        exact.canonical_shapes = true;
+        let mut moved = shape_rule(GeometryMode::Translation);
+        moved.canonical_shapes = true;
+        let hash = |rule: &MatchingRule, tag: &Tag| {
+            TreeHash::calc_hash(rule, tag, &HashContext::default(), &vec![])
+        };
+        assert_ne!(
+            hash(&shape_rule(GeometryMode::Exact), &rect),
+            hash(&shape_rule(GeometryMode::Exact), &path)
+        );
+        assert_eq!(hash(&exact, &rect), hash(&exact, &path));
+        assert_ne!(hash(&exact, &rect), hash(&exact, &moved_path));
+        assert_ne!(hash(&exact, &rect), hash(&exact, &circle));
+        assert_eq!(hash(&moved, &rect), hash(&moved, &moved_path));
+    }
+
+    #[test]
+    fn excluded_attribute_group() {
+        let mut rule = shape_rule(GeometryMode::Exact);
+        rule.attr.as_mut().unwrap().exclude_groups = ["paint".to_string()].into();
+        rule.resolve_attribute_groups(|name| MatchingRules::default().attribute_group(name));
+
+        let red = tag_with_args("rect", &[("fill", "red"), ("style", "stroke:red;width:3")]);
+        let blue = tag_with_args(
+            "rect",
+            &[("fill", "blue"), ("style", "stroke: blue; width:3")],
+        );
+        let wide = tag_with_args(
+            "rect",
+            &[("fill", "blue"), ("style", "stroke:blue;width:5")],
+        );
+        assert_eq!(
+            TreeHash::calc_hash(&rule, &red, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&rule, &blue, &HashContext::default(), &vec![])
+        );
+        assert_ne!(
+            TreeHash::calc_hash(&rule, &blue, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&rule, &wide, &HashContext::default(), &vec![])
+        );
+    }
+
+    #[test]
+    fn without_pos_and_style() {
+        // Without resolving the attribute groups
+        let mut rule = shape_rule(GeometryMode::Exact);
+        let attr = rule.attr.as_mut().unwrap();
+        attr.with_pos = false;
+        attr.with_style = false;
+
+        let a = tag_with_args("circle", &[("cx", "1"), ("fill", "red"), ("opacity", "1")]);
+        let b = tag_with_args("circle", &[("cx", "2"), ("fill", "blue"), ("opacity", "1")]);
+        let c = tag_with_args("circle", &[("cx", "2"), ("fill", "blue"), ("opacity", "0")]);
+        let hash = |tag: &Tag| TreeHash::calc_hash(&rule, tag, &HashContext::default(), &vec![]);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&b), hash(&c));
+    }
+
+    #[test]
+    fn normalized_values() {
+        let mut rule = shape_rule(GeometryMode::Exact);
+        rule.normalize = serde_yaml::from_str(
+            r#"
+            - {op: regex_replace, pattern: '_[0-9a-f]{4}$', attributes: [id]}
+            - {op: collapse_whitespace, text: true}
+            - {op: lowercase, text: true}
+            "#,
+        )
+        .unwrap();
+        let mut a = tag_with_args("g", &[("id", "cluster_a83f")]);
+        a.text = " User  Service".to_string();
+        let mut b = tag_with_args("g", &[("id", "cluster_b12c")]);
+        b.text = "user service".to_string();
+        let mut c = tag_with_args("g", &[("id", "node_b12c")]);
+        c.text = "user service".to_string();
+        assert_eq!(
+            TreeHash::calc_hash(&rule, &a, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&rule, &b, &HashContext::default(), &vec![])
+        );
+        assert_ne!(
+            TreeHash::calc_hash(&rule, &b, &HashContext::default(), &vec![]),
+            TreeHash::calc_hash(&rule, &c, &HashContext::default(), &vec![])
+        );
+    }
+
+    #[test]
+    fn context_rules() {
+        let svg = SVG::parse_svg_string(
+            r#"<svg>
+              <g id="a"><g><rect width="10"/><text>t</text></g></g>
+              <g id="b"><g><rect width="10"/><text>t</text></g></g>
+            </svg>"#,
+        )
+        .unwrap();
+        let rules: MatchingRules = serde_yaml::from_str(
+            r#"
+            rules:
+              - name: same_id
+                attr: {included_attr: [id], exclude_attr: []}
+                include_text: false
+                recursive: false
+              - name: in_group
+                apply_to_tags: [text]
+                include_text: false
+                recursive: false
+                parent_rule: all_without_subtrees
+                sibling: {offset: -1, rule: all}
+              - name: in_node
+                apply_to_tags: [text]
+                include_text: false
+                recursive: false
+                ancestor: {selector: "g[id]", rule: same_id}
+            priorities: [in_group, in_node]
+            "#,
+        )
+        .unwrap();
+        let hashes = TreeHash::build_for_svg(&svg, &rules.resolved_rules());
+        let mut texts = Vec::new();
+        hashes.for_each(|s| {
+            if s.value().0.name == "text" {
+                texts.push(s.value().1.clone());
+            }
+        });
+        assert_eq!(texts.len(), 2);
+        assert!(texts[0].eq_rule("in_group", &texts[1]));
+        assert!(!texts[0].eq_rule("in_node", &texts[1]));
+        assert!(texts[0].rule_hash("in_node").is_some());
+    }
 }